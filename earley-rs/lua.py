@@ -2,7 +2,7 @@
 this is a fairly poor but incremental lua parser
 
 * currently, since empty productions are not yet supported, trailing fieldsep's are not allowed
-* currently, the way we handle semicolons is by just trying to insert one if we get a parse error
+* currently, the way we handle semicolons is by letting the parser insert one if we get a parse error
 * strings are not supported
 * numbers and idents are kinda wonky
 * spaces are mostly ignored (so hello world and helloworld are not distinguishable)
@@ -280,20 +280,17 @@ g.add_prod("chunk", [nt("stats"), nt("laststat"), t(";")])
 # print(g)
 
 p = PrefixParser(g, initial="chunk")
+p.set_insertable([(";", 1)], 1)
 
 
 def push(tok: str):
     try:
-        p.try_next(tok)
+        inserted = p.try_next_inserting(tok)
+        if inserted:
+            print(f"inserted {inserted} before {tok}")
         print(p.legal_tokens())
     except ParseError as pe:
         print(f"could not add {tok}: {pe}")
-        print(f"trying to insert semicolon")
-        try:
-            p.try_next(";")
-            p.try_next(tok)
-        except ParseError as pe:
-            print(f"still does not work {pe}")
 
 keywords = {
     "do",
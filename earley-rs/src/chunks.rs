@@ -0,0 +1,149 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Display};
+
+use crate::{latex::ParseTree, table::Item, table::Table, ParseInfo, Parser, Range, Token};
+
+/// The result of [Parser::parse_chunks]
+pub struct Chunks<T> {
+    chart: ParseInfo<T>,
+    /// the nonterminal and range of every chunk, in order
+    chunks: Vec<(String, Range)>,
+}
+
+impl<T> Chunks<T>
+where
+    T: PartialEq + Display,
+{
+    /// the nonterminal and range of every chunk, in order
+    pub fn spans(&self) -> impl Iterator<Item = (&str, &Range)> {
+        self.chunks
+            .iter()
+            .map(|(name, range)| (name.as_str(), range))
+    }
+
+    /// the parse tree of every chunk, in order
+    pub fn trees(&self) -> Vec<ParseTree<'_, T>> {
+        self.chunks
+            .iter()
+            .map(|(name, range)| {
+                let (item, inserted_by) = self.chart.table[range.end]
+                    .iter()
+                    .find(|(item, _)| {
+                        item.name() == name && item.after().is_empty() && item.range() == range
+                    })
+                    .expect("chunks are completed items");
+                self.chart
+                    .reconstruct_tree(range.end, item, inserted_by)
+                    .into()
+            })
+            .collect()
+    }
+}
+
+/// (covered tokens, number of chunks) of a cover of a prefix, and its last chunk
+type Cover<'a, T> = ((usize, Reverse<usize>), Option<&'a Item<T>>);
+
+/// Of the complete items spanning the same range, the one that is not the only child of
+/// another, preferring the initial nonterminals
+fn outermost<'a, T>(items: &[&'a Item<T>], initials: &[String]) -> &'a Item<T> {
+    let below = |x: &Item<T>| {
+        items.iter().any(|y| {
+            y.name() != x.name() && matches!(y.before(), [Token::NonTerm(nt)] if nt == x.name())
+        })
+    };
+    let rank = |x: &Item<T>| initials.iter().position(|initial| initial == x.name());
+    items
+        .iter()
+        .copied()
+        .min_by_key(|x| (below(x), rank(x).is_none(), rank(x), x.name()))
+        .expect("there is at least one item")
+}
+
+impl<T, I> Parser<T, I>
+where
+    I: Iterator<Item = T>,
+    T: Clone + Eq + std::hash::Hash + Display,
+{
+    /// Covers as much of the input as possible with non-overlapping constituents.
+    ///
+    /// The initial nonterminals are predicted at every position, so the chart contains every
+    /// complete item that derives from them, wherever it starts. Out of those, the chunks cover
+    /// the most tokens, using as few chunks as possible. If the whole input parses, that is a
+    /// single chunk.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::NonTerm("S".into()), Token::NonTerm("S".into())]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// let chunks = Parser::new("()(()))(()".chars(), grammar, "S").parse_chunks();
+    /// let spans: Vec<_> = chunks.spans().map(|(_, range)| range.clone()).collect();
+    /// assert_eq!(spans, [0..6, 8..10]);
+    /// assert_eq!(chunks.trees().len(), 2);
+    /// ```
+    pub fn parse_chunks(mut self) -> Chunks<T> {
+        self.table = self.table.seed_everywhere();
+        for token in self.input.by_ref() {
+            self.table.next(token);
+        }
+        let Table {
+            table,
+            grammar,
+            initials,
+            ..
+        } = self.table;
+        let n = table.len() - 1;
+
+        // the complete items ending at every position, grouped by where they start
+        let mut ending: Vec<BTreeMap<usize, Vec<&Item<T>>>> = vec![BTreeMap::new(); n + 1];
+        for (end, set) in table.iter().enumerate() {
+            for item in set.keys() {
+                if item.after().is_empty() && !item.range().is_empty() {
+                    ending[end]
+                        .entry(item.range().start)
+                        .or_default()
+                        .push(item);
+                }
+            }
+        }
+
+        // best[k] is the best cover of the first k tokens, on ties the longest last chunk wins
+        let mut best: Vec<Cover<T>> = vec![((0, Reverse(0)), None)];
+        for end in 1..=n {
+            let mut candidate = (best[end - 1].0, None);
+            for (&start, items) in &ending[end] {
+                let ((covered, Reverse(chunks)), _) = best[start];
+                let score = (covered + end - start, Reverse(chunks + 1));
+                if score > candidate.0 {
+                    candidate = (score, Some(outermost(items, &initials)));
+                }
+            }
+            best.push(candidate);
+        }
+
+        let mut chunks = Vec::new();
+        let mut end = n;
+        while end > 0 {
+            match best[end].1 {
+                Some(item) => {
+                    chunks.push((item.name().to_owned(), item.range().clone()));
+                    end = item.range().start;
+                }
+                None => end -= 1,
+            }
+        }
+        chunks.reverse();
+
+        let initial = initials.into_iter().next().unwrap_or_default();
+        Chunks {
+            chart: ParseInfo {
+                table,
+                grammar,
+                initial,
+            },
+            chunks,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt::Display,
+};
+
+use crate::{table::Item, table::Table, Grammar, Token};
+
+impl<T: Clone> Grammar<T> {
+    /// the shortest string every productive nonterminal derives
+    pub(crate) fn shortest_yields(&self) -> HashMap<&str, Vec<T>> {
+        let mut out: HashMap<&str, Vec<T>> = HashMap::new();
+        loop {
+            let mut changed = false;
+            for (name, productions) in &self.productions {
+                for production in productions {
+                    let Some(candidate) = yield_of(&out, production.iter()) else {
+                        continue;
+                    };
+                    if out
+                        .get(name.as_str())
+                        .is_none_or(|x| candidate.len() < x.len())
+                    {
+                        out.insert(name, candidate);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                return out;
+            }
+        }
+    }
+}
+
+/// concatenates the shortest yields of `symbols`, if all of them are productive
+fn yield_of<'a, T: Clone + 'a>(
+    yields: &HashMap<&str, Vec<T>>,
+    symbols: impl IntoIterator<Item = &'a Token<T>>,
+) -> Option<Vec<T>> {
+    let mut out = Vec::new();
+    for symbol in symbols {
+        match symbol {
+            Token::Term(t) => out.push(t.clone()),
+            Token::NonTerm(nt) => out.extend(yields.get(nt.as_str())?.iter().cloned()),
+        }
+    }
+    Some(out)
+}
+
+/// a nonterminal and the set it was predicted in
+type Node<'a> = (&'a str, usize);
+
+/// the symbols of `item` after the next one, in order
+fn rest<T>(item: &Item<T>) -> impl Iterator<Item = &Token<T>> {
+    let after = item.after();
+    after[..after.len().saturating_sub(1)].iter().rev()
+}
+
+impl<T> Table<T>
+where
+    T: Clone + Eq + std::hash::Hash + Display,
+{
+    /// A shortest sequence of tokens that completes the parse.
+    ///
+    /// Once the nonterminal `A` that was predicted in set `i` is complete, some item in set `i`
+    /// that expects `A` advances. The cheapest way to then complete the start symbol only depends
+    /// on `(A, i)`, so we run dijkstra on those pairs, starting from `(initial, 0)` for every initial nonterminal.
+    pub(super) fn shortest_completion(&self) -> Option<Vec<T>> {
+        let yields = self.grammar.shortest_yields();
+
+        // (B, k) -> [(A, i, p)], where p in set i is a `B` item from `k` expecting `A`
+        let mut edges: HashMap<Node, Vec<(Node, &Item<T>)>> = HashMap::new();
+        for (i, set) in self.table.iter().enumerate() {
+            for item in set.keys() {
+                let Some(Token::NonTerm(next)) = item.after().last() else {
+                    continue;
+                };
+                edges
+                    .entry((item.name(), item.range().start))
+                    .or_default()
+                    .push(((next.as_str(), i), item));
+            }
+        }
+
+        let starts: Vec<Node> = self.initials.iter().map(|x| (x.as_str(), 0)).collect();
+        let mut dist: HashMap<Node, usize> = starts.iter().map(|&x| (x, 0)).collect();
+        let mut pred: HashMap<Node, &Item<T>> = HashMap::new();
+        let mut queue: BinaryHeap<_> = starts.iter().map(|&x| Reverse((0, x))).collect();
+        while let Some(Reverse((d, node))) = queue.pop() {
+            if dist[&node] < d {
+                continue;
+            }
+            for &(next, item) in edges.get(&node).into_iter().flatten() {
+                let Some(cost) = yield_of(&yields, rest(item)) else {
+                    continue;
+                };
+                let d = d + cost.len();
+                if dist.get(&next).is_none_or(|&x| d < x) {
+                    dist.insert(next, d);
+                    pred.insert(next, item);
+                    queue.push(Reverse((d, next)));
+                }
+            }
+        }
+
+        let (mut out, mut node) = self
+            .table
+            .last()
+            .unwrap()
+            .keys()
+            .filter_map(|item| {
+                let node = (item.name(), item.range().start);
+                let completion = yield_of(&yields, item.after().iter().rev())?;
+                Some((completion.len() + dist.get(&node)?, completion, node))
+            })
+            .min_by_key(|(len, ..)| *len)
+            .map(|(_, completion, node)| (completion, node))?;
+
+        while !starts.contains(&node) {
+            let item = pred[&node];
+            out.extend(yield_of(&yields, rest(item)).expect("only productive items are used"));
+            node = (item.name(), item.range().start);
+        }
+        Some(out)
+    }
+}
@@ -0,0 +1,554 @@
+//! A [serde] deserializer over parse trees
+//!
+//! The tree is mapped onto the data model like so:
+//!
+//! - structs and maps: the nonterminal children of a node, keyed by their name
+//! - enums: the variant is the name of the only nonterminal child, the name of the node itself
+//!   or the text of the node, whichever matches first
+//! - sequences: the nonterminal children of a node, where children with the same name as the
+//!   node are flattened. This way `list -> item | list ',' item` yields all the `item`s
+//! - tuples: the nonterminal children of a node
+//! - primitives and strings: the concatenated terminals below a node, parsed with [FromStr]
+//! - options: `None` if there are no terminals below a node
+//!
+//! Terminals in between (separators, keywords, ...) are skipped.
+//!
+//! # Examples
+//!
+//! ```
+//! # use earley::*;
+//! # use serde::Deserialize;
+//! # fn t(c: char) -> Token<char> {
+//! #     Token::Term(c)
+//! # }
+//! # fn nt(c: impl ToString) -> Token<char> {
+//! #     Token::NonTerm(c.to_string())
+//! # }
+//! let mut grammar = Grammar::new();
+//! grammar.add_prod("pair", [nt("left"), t(','), nt("right")]);
+//! grammar.add_prod("left", [nt("num")]);
+//! grammar.add_prod("right", [nt("num")]);
+//! grammar.add_prod("num", [nt("digit")]);
+//! grammar.add_prod("num", [nt("num"), nt("digit")]);
+//! for d in '0'..='9' {
+//!     grammar.add_prod("digit", [t(d)]);
+//! }
+//!
+//! #[derive(Deserialize)]
+//! struct Pair {
+//!     left: u32,
+//!     right: u32,
+//! }
+//!
+//! let parse = Parser::new("12,345".chars(), grammar, "pair").parse().unwrap();
+//! let pair: Pair = earley::de::from_tree(&parse.tree()).unwrap();
+//! assert_eq!((pair.left, pair.right), (12, 345));
+//! ```
+
+use std::{fmt::Display, str::FromStr};
+
+use serde::de::{
+    self, value::StrDeserializer, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor,
+};
+
+use crate::latex::ParseTree;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{0}")]
+    Custom(String),
+    #[error("cannot parse {text:?} as {expected}")]
+    Parse {
+        text: String,
+        expected: &'static str,
+    },
+}
+
+impl de::Error for Error {
+    fn custom<M: Display>(msg: M) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+/// deserializes an instance of `D` from a parse tree
+pub fn from_tree<D, T>(tree: &ParseTree<'_, T>) -> Result<D, Error>
+where
+    D: DeserializeOwned,
+    T: Display,
+{
+    D::deserialize(Deserializer::new(tree))
+}
+
+pub struct Deserializer<'t, 'a, T> {
+    tree: &'t ParseTree<'a, T>,
+}
+
+impl<T> Clone for Deserializer<'_, '_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Deserializer<'_, '_, T> {}
+
+impl<'t, 'a, T: Display> Deserializer<'t, 'a, T> {
+    pub fn new(tree: &'t ParseTree<'a, T>) -> Self {
+        Self { tree }
+    }
+
+    /// the terminals below the node
+    fn text(&self) -> String {
+        fn go<T: Display>(tree: &ParseTree<'_, T>, out: &mut String) {
+            use std::fmt::Write;
+            match tree {
+                ParseTree::Terminal(t) => write!(out, "{t}").unwrap(),
+                ParseTree::Error(..) => {}
+                ParseTree::NonTerminal(_, children) => {
+                    for child in children {
+                        go(child, out);
+                    }
+                }
+            }
+        }
+        let mut out = String::new();
+        go(self.tree, &mut out);
+        out
+    }
+
+    fn parse<P: FromStr>(&self, expected: &'static str) -> Result<P, Error> {
+        let text = self.text();
+        text.parse().map_err(|_| Error::Parse { text, expected })
+    }
+
+    fn nonterminals(&self) -> impl Iterator<Item = (&'a str, &'t ParseTree<'a, T>)> {
+        let children = match self.tree {
+            ParseTree::Terminal(_) | ParseTree::Error(..) => &[][..],
+            ParseTree::NonTerminal(_, children) => &children[..],
+        };
+        children.iter().filter_map(|child| match child {
+            ParseTree::Terminal(_) => None,
+            ParseTree::NonTerminal(name, _) | ParseTree::Error(name, _) => Some((*name, child)),
+        })
+    }
+
+    /// the elements of a repetition, see the module documentation
+    fn elements(&self) -> Vec<&'t ParseTree<'a, T>> {
+        fn go<'t, 'a, T: Display>(
+            name: &str,
+            de: Deserializer<'t, 'a, T>,
+            out: &mut Vec<&'t ParseTree<'a, T>>,
+        ) {
+            for (child_name, child) in de.nonterminals() {
+                if child_name == name {
+                    go(name, Deserializer::new(child), out);
+                } else {
+                    out.push(child);
+                }
+            }
+        }
+        let mut out = Vec::new();
+        if let ParseTree::NonTerminal(name, _) = self.tree {
+            go(name, *self, &mut out);
+        }
+        out
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+                visitor.$visit(self.parse(stringify!($method))?)
+            }
+        )*
+    };
+}
+
+impl<'de, T: Display> de::Deserializer<'de> for Deserializer<'_, '_, T> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.nonterminals().next().is_some() {
+            self.deserialize_map(visitor)
+        } else {
+            visitor.visit_string(self.text())
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.text())
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_string(self.text())
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_byte_buf(self.text().into_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_byte_buf(self.text().into_bytes())
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.text().is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_seq(SeqAccess(self.elements().into_iter()))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let children: Vec<_> = self.nonterminals().map(|(_, child)| child).collect();
+        visitor.visit_seq(SeqAccess(children.into_iter()))
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        let children: Vec<_> = self.nonterminals().collect();
+        visitor.visit_map(MapAccess {
+            children: children.into_iter(),
+            value: None,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        let mut nonterminals = self.nonterminals();
+        let only_child = match (nonterminals.next(), nonterminals.next()) {
+            (Some(child), None) => Some(child),
+            _ => None,
+        };
+        let own_name = match self.tree {
+            ParseTree::NonTerminal(name, _) | ParseTree::Error(name, _) => Some((*name, self.tree)),
+            ParseTree::Terminal(_) => None,
+        };
+        let text = self.text();
+        let (variant, content) = only_child
+            .into_iter()
+            .chain(own_name)
+            .find(|(name, _)| variants.contains(name))
+            .map(|(name, content)| (name.to_owned(), content))
+            .unwrap_or((text, self.tree));
+
+        visitor.visit_enum(EnumAccess {
+            variant,
+            content: Deserializer::new(content),
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqAccess<I>(I);
+
+impl<'de, 't, 'a: 't, T, I> de::SeqAccess<'de> for SeqAccess<I>
+where
+    T: Display + 'a,
+    I: Iterator<Item = &'t ParseTree<'a, T>>,
+{
+    type Error = Error;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        self.0
+            .next()
+            .map(|tree| seed.deserialize(Deserializer::new(tree)))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.0.size_hint() {
+            (lo, Some(hi)) if lo == hi => Some(lo),
+            _ => None,
+        }
+    }
+}
+
+struct MapAccess<'t, 'a, T, I> {
+    children: I,
+    value: Option<&'t ParseTree<'a, T>>,
+}
+
+impl<'de, 't, 'a: 't, T, I> de::MapAccess<'de> for MapAccess<'t, 'a, T, I>
+where
+    T: Display + 'a,
+    I: Iterator<Item = (&'a str, &'t ParseTree<'a, T>)>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((name, child)) = self.children.next() else {
+            return Ok(None);
+        };
+        self.value = Some(child);
+        let key: StrDeserializer<'_, Error> = name.into_deserializer();
+        seed.deserialize(key).map(Some)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .expect("next_value_seed is called after next_key_seed");
+        seed.deserialize(Deserializer::new(value))
+    }
+}
+
+struct EnumAccess<'t, 'a, T> {
+    variant: String,
+    content: Deserializer<'t, 'a, T>,
+}
+
+impl<'de, 't, 'a, T: Display> de::EnumAccess<'de> for EnumAccess<'t, 'a, T> {
+    type Error = Error;
+    type Variant = Deserializer<'t, 'a, T>;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<(S::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((variant, self.content))
+    }
+}
+
+impl<'de, T: Display> de::VariantAccess<'de> for Deserializer<'_, '_, T> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(
+        self,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_struct(self, "", fields, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use crate::{nt, t, Grammar, Parser};
+
+    /// config  ::= entries
+    /// entries ::= entry | entries ";" entry
+    /// entry   ::= key "=" value
+    /// key     ::= letter | key letter
+    /// value   ::= number | flag | "(" pair ")"
+    /// pair    ::= number "," number
+    /// flag    ::= "y" | "n"
+    /// number  ::= digit | number digit
+    fn config() -> Grammar<String> {
+        let mut grammar = Grammar::new();
+        grammar.add_prod("config", [nt("entries")]);
+        grammar.add_prod("entries", [nt("entry")]);
+        grammar.add_prod("entries", [nt("entries"), t(';'), nt("entry")]);
+        grammar.add_prod("entry", [nt("key"), t('='), nt("value")]);
+        grammar.add_prod("key", [nt("letter")]);
+        grammar.add_prod("key", [nt("key"), nt("letter")]);
+        grammar.add_prod("value", [nt("number")]);
+        grammar.add_prod("value", [nt("flag")]);
+        grammar.add_prod("value", [t('('), nt("pair"), t(')')]);
+        grammar.add_prod("pair", [nt("number"), t(','), nt("number")]);
+        grammar.add_prod("flag", [t('y')]);
+        grammar.add_prod("flag", [t('n')]);
+        grammar.add_prod("number", [nt("digit")]);
+        grammar.add_prod("number", [nt("number"), nt("digit")]);
+        for c in 'a'..='z' {
+            grammar.add_prod("letter", [t(c)]);
+        }
+        for c in '0'..='9' {
+            grammar.add_prod("digit", [t(c)]);
+        }
+        grammar
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        entries: Vec<Entry>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Entry {
+        key: String,
+        value: Value,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Value {
+        Number(u32),
+        Flag(Flag),
+        Pair(u32, u32),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    enum Flag {
+        #[serde(rename = "y")]
+        Yes,
+        #[serde(rename = "n")]
+        No,
+    }
+
+    fn parse_config(input: &str) -> Result<Config, super::Error> {
+        let parse = Parser::new(input.chars().map(|c| c.to_string()), config(), "config")
+            .parse()
+            .unwrap();
+        super::from_tree(&parse.tree())
+    }
+
+    #[test]
+    fn config_file() {
+        let config = parse_config("width=80;wrap=y;size=(3,42)").unwrap();
+        assert_eq!(
+            config,
+            Config {
+                entries: vec![
+                    Entry {
+                        key: "width".into(),
+                        value: Value::Number(80)
+                    },
+                    Entry {
+                        key: "wrap".into(),
+                        value: Value::Flag(Flag::Yes)
+                    },
+                    Entry {
+                        key: "size".into(),
+                        value: Value::Pair(3, 42)
+                    },
+                ]
+            }
+        );
+    }
+
+    #[test]
+    fn missing_field() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Strict {
+            entries: Vec<Entry>,
+            version: u32,
+        }
+        let parse = Parser::new("a=1".chars().map(|c| c.to_string()), config(), "config")
+            .parse()
+            .unwrap();
+        assert!(super::from_tree::<Strict, _>(&parse.tree()).is_err());
+    }
+
+    #[test]
+    fn empty_production_is_none() {
+        let mut grammar = Grammar::new();
+        grammar.add_prod("opt", [nt("digit")]);
+        grammar.add_prod("opt", []);
+        grammar.add_prod("digit", [t('7')]);
+
+        for (input, expected) in [("", None), ("7", Some(7))] {
+            let parse = Parser::new(input.chars().map(|c| c.to_string()), grammar.clone(), "opt")
+                .parse()
+                .unwrap();
+            let value: Option<u8> = super::from_tree(&parse.tree()).unwrap();
+            assert_eq!(value, expected);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Display,
+    sync::Arc,
+};
+
+use crate::{table::Item, table::Table, Error, Grammar, InsertedBy, ParseInfo, Range};
+
+/// A sequence of tokens that can be edited anywhere, keeping its parse up to date.
+///
+/// After an edit, the Earley sets before the edit are kept. The sets after it are recomputed
+/// until one of them matches the corresponding set from before the edit, from which point on
+/// the old sets are reused.
+///
+/// # Examples
+///
+/// ```
+/// # use earley::*;
+/// let mut grammar = Grammar::new();
+/// grammar.add_prod("S", [Token::NonTerm("S".into()), Token::NonTerm("S".into())]);
+/// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+/// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+/// let mut document = Document::new("()(())".chars(), grammar, "S");
+/// assert!(document.is_complete());
+///
+/// document.edit(3..3, "()".chars());
+/// assert_eq!(document.tokens(), &"()(()())".chars().collect::<Vec<_>>());
+/// assert!(document.is_complete());
+///
+/// document.edit(0..1, []);
+/// assert!(!document.is_complete());
+/// ```
+pub struct Document<T> {
+    tokens: Vec<T>,
+    pub(super) table: Table<T>,
+}
+
+impl<T> Document<T>
+where
+    T: Clone + Eq + std::hash::Hash + Display,
+{
+    pub fn new(
+        tokens: impl IntoIterator<Item = T>,
+        grammar: impl Into<Arc<Grammar<T>>>,
+        initial: impl AsRef<str>,
+    ) -> Self {
+        let tokens: Vec<T> = tokens.into_iter().collect();
+        let mut table = Table::new(grammar, [initial], tokens.len());
+        for token in &tokens {
+            table.next(token.clone());
+        }
+        Self { tokens, table }
+    }
+
+    pub fn tokens(&self) -> &[T] {
+        &self.tokens
+    }
+
+    /// Replaces the tokens in `range` with `tokens`.
+    ///
+    /// Returns the number of Earley sets that had to be recomputed
+    pub fn edit(&mut self, range: Range, tokens: impl IntoIterator<Item = T>) -> usize {
+        let Range { mut start, mut end } = range;
+        let mut tokens: Vec<T> = tokens.into_iter().collect();
+        // pure insertions and deletions are widened by a token, so that the positions after the
+        // edit can't be confused with the position of the edit
+        if start == end || tokens.is_empty() {
+            if start > 0 {
+                start -= 1;
+                tokens.insert(0, self.tokens[start].clone());
+            } else if end < self.tokens.len() {
+                tokens.push(self.tokens[end].clone());
+                end += 1;
+            }
+        }
+        let new_end = start + tokens.len();
+        self.tokens.splice(start..end, tokens);
+
+        // old[k - start - 1] is the old set k
+        let mut old = self.table.table.split_off(start + 1);
+        let map = |pos: usize| match pos {
+            pos if pos <= start => pos,
+            pos if pos >= end => pos - end + new_end,
+            _ => unreachable!("no item refers to a position inside the edit"),
+        };
+
+        // matches[k - new_end] is whether the new set k matches its old set
+        let mut matches = Vec::new();
+        let mut recomputed = 0;
+        for idx in start..self.tokens.len() {
+            self.table.next(self.tokens[idx].clone());
+            recomputed += 1;
+
+            let k = idx + 1;
+            if k < new_end {
+                continue;
+            }
+            let k_old = k - new_end + end;
+            let old_set = if k_old == start {
+                &self.table.table[start]
+            } else {
+                &old[k_old - start - 1]
+            };
+            let set = &self.table.table[k];
+            matches.push(sets_match(set, old_set, start..end, start..new_end, map));
+            if !matches[k - new_end] || !self.origins_match(k, start, new_end, &matches) {
+                continue;
+            }
+
+            let rest = old.split_off(k_old - start);
+            if new_end == end {
+                self.table.table.extend(rest);
+            } else {
+                self.table.table.extend(rest.iter().map(|set| {
+                    let set = set
+                        .iter()
+                        .map(|(item, inserted_by)| {
+                            (item.map_range(map), inserted_by.map_ranges(map))
+                        })
+                        .collect();
+                    Arc::new(set)
+                }));
+            }
+            break;
+        }
+        recomputed
+    }
+
+    /// Whether all sets after the edit, that items in set `k` may depend on, match.
+    ///
+    /// Completing an item looks into the set it starts in, and the items found there may in
+    /// turn be completed later on
+    fn origins_match(&self, k: usize, start: usize, new_end: usize, matches: &[bool]) -> bool {
+        let mut seen = HashSet::from([k]);
+        let mut todo = vec![k];
+        while let Some(j) = todo.pop() {
+            for item in self.table.table[j].keys() {
+                let origin = item.range().start;
+                if origin <= start || !seen.insert(origin) {
+                    continue;
+                }
+                if !matches[origin - new_end] {
+                    return false;
+                }
+                todo.push(origin);
+            }
+        }
+        true
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.table.is_complete()
+    }
+
+    pub fn parse(&self) -> Result<ParseInfo<T>, Error> {
+        self.table.clone().into_parse().ok_or(Error)
+    }
+}
+
+/// Whether the `new` set is the `old` set with its positions moved by `map`, and neither has
+/// items starting inside the edit.
+///
+/// See [Document::origins_match] for when this means that all later sets match as well.
+fn sets_match<T: Clone + Eq + std::hash::Hash>(
+    new: &HashMap<Item<T>, InsertedBy>,
+    old: &HashMap<Item<T>, InsertedBy>,
+    old_edit: Range,
+    new_edit: Range,
+    map: impl Fn(usize) -> usize,
+) -> bool {
+    let inside = |edit: &Range, item: &Item<T>| {
+        edit.start < item.range().start && item.range().start < edit.end
+    };
+    new.len() == old.len()
+        && !new.keys().any(|item| inside(&new_edit, item))
+        && !old.keys().any(|item| inside(&old_edit, item))
+        && old
+            .keys()
+            .all(|item| new.contains_key(&item.map_range(&map)))
+}
+
+impl InsertedBy {
+    fn map_ranges(&self, f: impl Fn(usize) -> usize) -> Self {
+        let map = |r: &Range| f(r.start)..f(r.end);
+        match self {
+            InsertedBy::Pred => InsertedBy::Pred,
+            InsertedBy::Scan(r) => InsertedBy::Scan(map(r)),
+            InsertedBy::Comp(r, s) => InsertedBy::Comp(map(r), map(s)),
+            InsertedBy::Error => InsertedBy::Error,
+        }
+    }
+}
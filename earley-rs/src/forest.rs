@@ -0,0 +1,371 @@
+use std::{collections::HashMap, fmt::Display, hash::Hash, rc::Rc, sync::Arc};
+
+use crate::{latex::ParseTree, table::Item, Grammar, InsertedBy, ParseInfo, Token};
+
+/// How a node derives the symbols before its dot
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Edge {
+    /// there are none
+    Empty,
+    /// from the node before its last terminal
+    Scan(usize),
+    /// from the node before its last nonterminal, and the complete node of that nonterminal
+    Comp(usize, usize),
+}
+
+/// All derivations of a parse, as a graph on the items of the chart.
+///
+/// The chart only remembers one way every item was inserted, so the edges are found by looking
+/// up the items that could have been combined into it.
+pub(crate) struct Forest<'a, T> {
+    /// the set and item of every node
+    pub(crate) nodes: Vec<(usize, &'a Item<T>, &'a InsertedBy)>,
+    /// the ways to derive every node
+    pub(crate) edges: Vec<Vec<Edge>>,
+    /// the log weight of the production of every complete node, 0 for the others
+    pub(crate) weights: Vec<f64>,
+    /// the complete items of the start symbol that span the whole input
+    pub(crate) roots: Vec<usize>,
+}
+
+type Chart<T> = [Arc<HashMap<Item<T>, InsertedBy>>];
+
+impl<'a, T> Forest<'a, T>
+where
+    T: Clone + Eq + Hash,
+{
+    pub(crate) fn new(
+        table: &'a Chart<T>,
+        grammar: &Grammar<T>,
+        roots: impl IntoIterator<Item = &'a Item<T>>,
+    ) -> Self {
+        // (set, nonterminal) -> the complete items in the set
+        let mut complete: HashMap<(usize, &str), Vec<&Item<T>>> = HashMap::new();
+        for (j, set) in table.iter().enumerate() {
+            for item in set.keys().filter(|item| item.after().is_empty()) {
+                complete.entry((j, item.name())).or_default().push(item);
+            }
+        }
+
+        let mut out = Self {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+            weights: Vec::new(),
+            roots: Vec::new(),
+        };
+        let mut ids: HashMap<(usize, &Item<T>), usize> = HashMap::new();
+        let mut id = |out: &mut Self, j: usize, item: &'a Item<T>| {
+            *ids.entry((j, item)).or_insert_with(|| {
+                let inserted_by = &table[j][item];
+                out.nodes.push((j, item, inserted_by));
+                let weight = match item.after() {
+                    [] => grammar
+                        .log_weight(item.name(), item.before())
+                        .unwrap_or(0.0),
+                    _ => 0.0,
+                };
+                out.weights.push(weight);
+                out.nodes.len() - 1
+            })
+        };
+        let end = table.len() - 1;
+        for root in roots {
+            let root = id(&mut out, end, root);
+            out.roots.push(root);
+        }
+
+        // the nodes are visited in the order they are found
+        let mut node = 0;
+        while node < out.nodes.len() {
+            let (j, item, inserted_by) = out.nodes[node];
+            let lookup = |k: usize| table[k].get_key_value(&item.retreat(k)).map(|x| x.0);
+            let mut edges = Vec::new();
+            match item.before().last() {
+                _ if matches!(inserted_by, InsertedBy::Error) => edges.push(Edge::Empty),
+                None => edges.push(Edge::Empty),
+                Some(Token::Term(_)) => {
+                    let prefix = lookup(j - 1).expect("scanned items have a prefix");
+                    edges.push(Edge::Scan(id(&mut out, j - 1, prefix)));
+                }
+                Some(Token::NonTerm(nt)) => {
+                    let children = complete.get(&(j, nt.as_str())).into_iter().flatten();
+                    for &child in children {
+                        let k = child.range().start;
+                        if k < item.range().start {
+                            continue;
+                        }
+                        let Some(prefix) = lookup(k) else {
+                            continue;
+                        };
+                        let prefix = id(&mut out, k, prefix);
+                        let child = id(&mut out, j, child);
+                        edges.push(Edge::Comp(prefix, child));
+                    }
+                }
+            }
+            out.edges.push(edges);
+            node += 1;
+        }
+        out
+    }
+
+    /// The `k` best derivations of every node, best first.
+    ///
+    /// The nodes are handled in the order of [Forest::groups], and the nodes of a group, which
+    /// may depend on each other, are repeated until nothing changes
+    pub(crate) fn k_best(&self, k: usize) -> Vec<Vec<Rc<Derivation>>> {
+        let mut best = vec![Vec::new(); self.nodes.len()];
+        for group in self.groups() {
+            // cycles that don't lower the weight would otherwise never stop
+            for _ in 0..=group.len() * k {
+                let mut changed = false;
+                for &node in &group {
+                    let candidates = self.candidates(node, &best, k);
+                    if better(&candidates, &best[node]) {
+                        best[node] = candidates;
+                        changed = true;
+                    }
+                }
+                if !changed {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    /// The nodes, such that every node only depends on nodes in earlier groups or in its own
+    /// group.
+    ///
+    /// A node only depends on nodes in earlier sets, or on nodes in the same set that start at
+    /// or after it, so the groups are the nodes with the same range
+    fn groups(&self) -> Vec<Vec<usize>> {
+        let mut order: Vec<usize> = (0..self.nodes.len()).collect();
+        let key = |node: usize| {
+            let (j, item, _) = self.nodes[node];
+            (j, std::cmp::Reverse(item.range().start))
+        };
+        order.sort_by_key(|&node| key(node));
+        order
+            .chunk_by(|&a, &b| key(a) == key(b))
+            .map(|group| group.to_vec())
+            .collect()
+    }
+
+    /// The sum of the weights of all derivations of every node, given the weight of every node,
+    /// i.e. the probability of the production of every complete node and 1 for the others
+    pub(crate) fn inside(&self, weights: &[f64]) -> Vec<f64> {
+        let mut inside = vec![0.0; self.nodes.len()];
+        for group in self.groups() {
+            settle(&group, &mut inside, |node, inside| {
+                let sum: f64 = self.edges[node]
+                    .iter()
+                    .map(|&e| match e {
+                        Edge::Empty => 1.0,
+                        Edge::Scan(prefix) => inside[prefix],
+                        Edge::Comp(prefix, child) => inside[prefix] * inside[child],
+                    })
+                    .sum();
+                weights[node] * sum
+            });
+        }
+        inside
+    }
+
+    /// The sum of the weights of the rest of all derivations of a root that use every node, so
+    /// that the product with [Forest::inside] is the weight of all derivations using it
+    pub(crate) fn outside(&self, weights: &[f64], inside: &[f64]) -> Vec<f64> {
+        // the nodes that have an edge to every node, with the other node of that edge
+        let mut uses = vec![Vec::new(); self.nodes.len()];
+        for (node, edges) in self.edges.iter().enumerate() {
+            for &e in edges {
+                match e {
+                    Edge::Empty => {}
+                    Edge::Scan(prefix) => uses[prefix].push((node, None)),
+                    Edge::Comp(prefix, child) => {
+                        uses[prefix].push((node, Some(child)));
+                        uses[child].push((node, Some(prefix)));
+                    }
+                }
+            }
+        }
+
+        let mut outside = vec![0.0; self.nodes.len()];
+        for group in self.groups().into_iter().rev() {
+            settle(&group, &mut outside, |node, outside| {
+                let root = if self.roots.contains(&node) { 1.0 } else { 0.0 };
+                let sum: f64 = uses[node]
+                    .iter()
+                    .map(|&(parent, other)| {
+                        outside[parent] * weights[parent] * other.map_or(1.0, |x| inside[x])
+                    })
+                    .sum();
+                root + sum
+            });
+        }
+        outside
+    }
+
+    /// the `k` best derivations of `node`, given those of the other nodes
+    fn candidates(
+        &self,
+        node: usize,
+        best: &[Vec<Rc<Derivation>>],
+        k: usize,
+    ) -> Vec<Rc<Derivation>> {
+        let mut out = Vec::new();
+        for (edge, &e) in self.edges[node].iter().enumerate() {
+            let weight = self.weights[node];
+            match e {
+                Edge::Empty => out.push(Derivation {
+                    score: weight,
+                    edge,
+                    parts: (None, None),
+                }),
+                Edge::Scan(prefix) => {
+                    for d in &best[prefix] {
+                        out.push(Derivation {
+                            score: d.score + weight,
+                            edge,
+                            parts: (Some(d.clone()), None),
+                        });
+                    }
+                }
+                Edge::Comp(prefix, child) => {
+                    for d in &best[prefix] {
+                        for c in &best[child] {
+                            out.push(Derivation {
+                                score: d.score + c.score + weight,
+                                edge,
+                                parts: (Some(d.clone()), Some(c.clone())),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        out.sort_by(|a, b| b.score.total_cmp(&a.score));
+        out.truncate(k);
+        out.into_iter().map(Rc::new).collect()
+    }
+
+    /// the tree of `derivation` of `node`
+    pub(crate) fn tree(&self, node: usize, derivation: &Derivation) -> ParseTree<'a, T> {
+        let (_, item, inserted_by) = self.nodes[node];
+        if let InsertedBy::Error = inserted_by {
+            return ParseTree::Error(item.name(), item.range().clone());
+        }
+        let mut children = Vec::new();
+        self.children(node, derivation, &mut children);
+        ParseTree::NonTerminal(item.name(), children)
+    }
+
+    /// the subtrees of the symbols before the dot of `node`
+    fn children(&self, node: usize, derivation: &Derivation, out: &mut Vec<ParseTree<'a, T>>) {
+        let (_, item, _) = self.nodes[node];
+        let (prefix_derivation, child_derivation) = &derivation.parts;
+        match self.edges[node][derivation.edge] {
+            Edge::Empty => {}
+            Edge::Scan(prefix) => {
+                self.children(prefix, prefix_derivation.as_ref().unwrap(), out);
+                out.push(ParseTree::Terminal(item.before().last().unwrap().term()));
+            }
+            Edge::Comp(prefix, child) => {
+                self.children(prefix, prefix_derivation.as_ref().unwrap(), out);
+                out.push(self.tree(child, child_derivation.as_ref().unwrap()));
+            }
+        }
+    }
+}
+
+/// Sets every node of `group` to `f` of the current values until they stop changing, which for
+/// cycles is only approximately
+fn settle(group: &[usize], values: &mut [f64], f: impl Fn(usize, &[f64]) -> f64) {
+    for _ in 0..1000 {
+        let mut changed = false;
+        for &node in group {
+            let value = f(node, values);
+            changed |= (value - values[node]).abs() > 1e-12 * value.abs();
+            values[node] = value;
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+/// A derivation of a node, by its log weight, the edge used, and the derivations of the nodes the
+/// edge points to.
+///
+/// Those derivations already existed when this one was made, so following them always ends,
+/// even where a cycle of productions costs nothing and a node's best derivations use the node
+#[derive(Debug)]
+pub(crate) struct Derivation {
+    pub(crate) score: f64,
+    edge: usize,
+    parts: (Option<Rc<Derivation>>, Option<Rc<Derivation>>),
+}
+
+/// whether the `new` derivations are strictly better than the `old` ones
+fn better(new: &[Rc<Derivation>], old: &[Rc<Derivation>]) -> bool {
+    for (a, b) in new.iter().zip(old) {
+        if a.score != b.score {
+            return a.score > b.score;
+        }
+    }
+    new.len() > old.len()
+}
+
+impl<T> ParseInfo<T>
+where
+    T: Clone + Eq + Hash + Display,
+{
+    pub(crate) fn forest(&self) -> Forest<'_, T> {
+        Forest::new(&self.table, &self.grammar, self.roots().map(|x| x.0))
+    }
+
+    /// The derivation with the highest weight, and the logarithm of its weight.
+    ///
+    /// The weight of a derivation is the product of the weights of the productions it uses, see
+    /// [crate::Grammar::add_weighted_prod]
+    pub fn best(&self) -> (ParseTree<'_, T>, f64) {
+        self.k_best(1)
+            .pop()
+            .expect("a complete parse has a derivation")
+    }
+
+    /// The `k` derivations with the highest weights, best first, and the logarithms of their
+    /// weights. There may be fewer than `k`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_weighted_prod("S", [Token::NonTerm("A".into())], 0.3);
+    /// grammar.add_weighted_prod("S", [Token::NonTerm("B".into())], 0.7);
+    /// grammar.add_prod("A", [Token::Term('x')]);
+    /// grammar.add_prod("B", [Token::Term('x')]);
+    ///
+    /// let parse = Parser::new("x".chars(), grammar, "S").parse().unwrap();
+    /// let derivations = parse.k_best(5);
+    /// assert_eq!(derivations.len(), 2);
+    /// assert_eq!(derivations[0].0.to_string(), "[S  [B  [{x}]]]");
+    /// assert!((derivations[1].1 - 0.3f64.ln()).abs() < 1e-9);
+    /// ```
+    pub fn k_best(&self, k: usize) -> Vec<(ParseTree<'_, T>, f64)> {
+        let forest = self.forest();
+        let best = forest.k_best(k);
+        let mut out: Vec<_> = forest
+            .roots
+            .iter()
+            .flat_map(|&root| (0..best[root].len()).map(move |rank| (root, rank)))
+            .map(|(root, rank)| (root, rank, best[root][rank].score))
+            .collect();
+        out.sort_by(|a, b| b.2.total_cmp(&a.2));
+        out.truncate(k);
+        out.into_iter()
+            .map(|(root, rank, score)| (forest.tree(root, &best[root][rank]), score))
+            .collect()
+    }
+}
@@ -0,0 +1,185 @@
+//! Random sentences of a grammar, e.g. for fuzzing
+
+use std::collections::HashMap;
+
+use crate::{latex::ParseTree, Grammar, Token};
+
+/// A source of randomness for [Generator]
+pub trait Rng {
+    /// a random number in `0.0..1.0`
+    fn next_f64(&mut self) -> f64;
+}
+
+impl<F: FnMut() -> f64> Rng for F {
+    fn next_f64(&mut self) -> f64 {
+        self()
+    }
+}
+
+/// Generates random sentences of a grammar, whose parse trees are at most `max_depth`
+/// nonterminals deep, and which are at most `max_len` tokens long.
+///
+/// Productions are chosen by their weights, among those that still allow staying within the
+/// limits. The deeper the tree gets, the less likely productions with more nonterminals are, so
+/// that recursive productions like `S -> S S` end before they run into the limits.
+///
+/// # Examples
+///
+/// ```
+/// # use earley::*;
+/// use earley::generate::Generator;
+///
+/// let mut grammar = Grammar::new();
+/// grammar.add_prod("S", [Token::NonTerm("S".into()), Token::NonTerm("S".into())]);
+/// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+/// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+/// let generator = Generator::new(&grammar, 10, 20);
+/// let mut state = 1u64;
+/// let mut rng = || {
+///     state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+///     (state >> 11) as f64 / (1u64 << 53) as f64
+/// };
+/// for _ in 0..10 {
+///     let sentence = generator.generate("S", &mut rng).unwrap();
+///     assert!(sentence.len() <= 20);
+///     assert!(Parser::new(sentence, grammar.clone(), "S").parse().is_ok());
+/// }
+/// ```
+pub struct Generator<'a, T> {
+    grammar: &'a Grammar<T>,
+    max_depth: usize,
+    max_len: usize,
+    /// `shortest[d][A]` is the length of the shortest sentence `A` derives with a tree at most
+    /// `d` deep, if there is one
+    shortest: Vec<HashMap<&'a str, usize>>,
+}
+
+impl<'a, T> Generator<'a, T> {
+    pub fn new(grammar: &'a Grammar<T>, max_depth: usize, max_len: usize) -> Self {
+        let mut shortest = vec![HashMap::new()];
+        for _ in 0..max_depth {
+            let below = shortest.last().unwrap();
+            let level: HashMap<&str, usize> = grammar
+                .productions
+                .iter()
+                .filter_map(|(name, productions)| {
+                    let len = productions
+                        .iter()
+                        .filter_map(|production| shortest_of(below, production))
+                        .min()?;
+                    Some((name.as_str(), len))
+                })
+                .collect();
+            shortest.push(level);
+        }
+        Self {
+            grammar,
+            max_depth,
+            max_len,
+            shortest,
+        }
+    }
+
+    /// A random sentence derived from `start`, if there is one within the limits
+    pub fn generate(&self, start: &str, rng: &mut impl Rng) -> Option<Vec<T>>
+    where
+        T: Clone,
+    {
+        let tree = self.generate_tree(start, rng)?;
+        let mut out = Vec::new();
+        leaves(&tree, &mut out);
+        Some(out)
+    }
+
+    /// Like [Generator::generate], but returns the parse tree of the sentence
+    pub fn generate_tree(&self, start: &str, rng: &mut impl Rng) -> Option<ParseTree<'a, T>> {
+        let (start, _) = self.grammar.productions.get_key_value(start)?;
+        if *self.shortest[self.max_depth].get(start.as_str())? > self.max_len {
+            return None;
+        }
+        Some(self.expand(start, self.max_depth, self.max_len, rng).0)
+    }
+
+    /// a tree of `name` at most `depth` deep and at most `budget` long, and its length. There
+    /// has to be one
+    fn expand(
+        &self,
+        name: &'a str,
+        depth: usize,
+        budget: usize,
+        rng: &mut impl Rng,
+    ) -> (ParseTree<'a, T>, usize) {
+        let below = &self.shortest[depth - 1];
+        let productions = &self.grammar.productions[name];
+        let weights = &self.grammar.weights[name];
+        // the deeper, the less likely are productions with many nonterminals
+        let bias = depth as f64 / self.max_depth as f64;
+        let candidates: Vec<(&Vec<Token<T>>, f64)> = productions
+            .iter()
+            .zip(weights)
+            .filter(|(production, _)| shortest_of(below, production).is_some_and(|x| x <= budget))
+            .map(|(production, weight)| {
+                let nonterminals = production
+                    .iter()
+                    .filter(|x| matches!(x, Token::NonTerm(_)))
+                    .count();
+                (production, weight * bias.powi(nonterminals as i32))
+            })
+            .collect();
+
+        let total: f64 = candidates.iter().map(|x| x.1).sum();
+        let mut choice = rng.next_f64() * total;
+        let (production, _) = candidates
+            .iter()
+            .find(|(_, weight)| {
+                choice -= weight;
+                choice < 0.0
+            })
+            .or(candidates.last())
+            .expect("a production stays within the limits");
+
+        // what the symbols after the current one need at least
+        let mut rest = shortest_of(below, production).unwrap();
+        let mut len = 0;
+        let mut children = Vec::new();
+        for symbol in production.iter() {
+            match symbol {
+                Token::Term(t) => {
+                    rest -= 1;
+                    len += 1;
+                    children.push(ParseTree::Terminal(t));
+                }
+                Token::NonTerm(nt) => {
+                    rest -= below[nt.as_str()];
+                    let (child, child_len) = self.expand(nt, depth - 1, budget - len - rest, rng);
+                    len += child_len;
+                    children.push(child);
+                }
+            }
+        }
+        (ParseTree::NonTerminal(name, children), len)
+    }
+}
+
+/// the length of the shortest sentence `production` derives, given those of the nonterminals
+fn shortest_of<T>(shortest: &HashMap<&str, usize>, production: &[Token<T>]) -> Option<usize> {
+    production
+        .iter()
+        .map(|symbol| match symbol {
+            Token::Term(_) => Some(1),
+            Token::NonTerm(nt) => shortest.get(nt.as_str()).copied(),
+        })
+        .sum()
+}
+
+fn leaves<T: Clone>(tree: &ParseTree<'_, T>, out: &mut Vec<T>) {
+    match tree {
+        ParseTree::Terminal(t) => out.push((*t).clone()),
+        ParseTree::NonTerminal(_, children) => {
+            for child in children {
+                leaves(child, out);
+            }
+        }
+        ParseTree::Error(..) => {}
+    }
+}
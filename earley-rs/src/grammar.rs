@@ -0,0 +1,113 @@
+//! Comparing the languages of grammars
+
+use std::{fmt::Display, hash::Hash};
+
+use crate::{Grammar, PrefixParser};
+
+/// A sentence only one of two grammars accepts, see [compare]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference<T> {
+    /// accepted by the first grammar only
+    OnlyA(Vec<T>),
+    /// accepted by the second grammar only
+    OnlyB(Vec<T>),
+}
+
+impl<T> Difference<T> {
+    pub fn sentence(&self) -> &[T] {
+        match self {
+            Difference::OnlyA(x) | Difference::OnlyB(x) => x,
+        }
+    }
+}
+
+/// Checks whether `a` and `b` derive the same sentences of at most `max_len` tokens from
+/// `start`.
+///
+/// Returns the shortest sentences only one of them accepts, sorted, which is empty if they agree
+/// up to `max_len`. The sentences of each grammar are enumerated with [Grammar::sentences] and
+/// parsed with the other one.
+///
+/// # Examples
+///
+/// ```
+/// # use earley::*;
+/// use earley::grammar::{compare, Difference};
+///
+/// let mut left = Grammar::new();
+/// left.add_prod("S", [Token::NonTerm("S".into()), Token::Term('+'), Token::Term('x')]);
+/// left.add_prod("S", [Token::Term('x')]);
+/// let mut right = Grammar::new();
+/// right.add_prod("S", [Token::Term('x'), Token::Term('+'), Token::NonTerm("S".into())]);
+/// right.add_prod("S", [Token::Term('x')]);
+/// assert!(compare(&left, &right, "S", 7).is_empty());
+///
+/// right.add_prod("S", [Token::Term('x'), Token::Term('+')]);
+/// assert_eq!(compare(&left, &right, "S", 7), [Difference::OnlyB(vec!['x', '+'])]);
+/// ```
+pub fn compare<T>(a: &Grammar<T>, b: &Grammar<T>, start: &str, max_len: usize) -> Vec<Difference<T>>
+where
+    T: Clone + Ord + Hash + Display,
+{
+    let only_a = shortest_rejected(b, start, a.sentences(start, max_len));
+    let max_len = only_a.first().map_or(max_len, |x| x.len());
+    let only_b = shortest_rejected(a, start, b.sentences(start, max_len));
+
+    let mut out: Vec<Difference<T>> = only_a
+        .into_iter()
+        .map(Difference::OnlyA)
+        .chain(only_b.into_iter().map(Difference::OnlyB))
+        .collect();
+    let len = out.iter().map(|x| x.sentence().len()).min();
+    out.retain(|x| Some(x.sentence().len()) == len);
+    out.sort_by(|x, y| x.sentence().cmp(y.sentence()));
+    out
+}
+
+/// The shortest of `sentences` that `grammar` does not accept, given in order of length.
+///
+/// Consecutive sentences often share a prefix, so the parser only goes back to where they
+/// differ
+fn shortest_rejected<T>(
+    grammar: &Grammar<T>,
+    start: &str,
+    sentences: impl Iterator<Item = Vec<T>>,
+) -> Vec<Vec<T>>
+where
+    T: Clone + Eq + Hash + Display,
+{
+    let mut parser = grammar
+        .productions
+        .contains_key(start)
+        .then(|| PrefixParser::new(grammar.clone(), start));
+    // the tokens the parser accepted
+    let mut accepted: Vec<T> = Vec::new();
+    let mut out: Vec<Vec<T>> = Vec::new();
+    for sentence in sentences {
+        if out.first().is_some_and(|x| x.len() < sentence.len()) {
+            break;
+        }
+        let accepts = parser.as_mut().is_some_and(|parser| {
+            let common = accepted
+                .iter()
+                .zip(&sentence)
+                .take_while(|(x, y)| x == y)
+                .count();
+            while accepted.len() > common {
+                parser.undo().expect("there are accepted tokens");
+                accepted.pop();
+            }
+            for token in &sentence[common..] {
+                if parser.try_next(token.clone()).is_err() {
+                    break;
+                }
+                accepted.push(token.clone());
+            }
+            accepted.len() == sentence.len() && parser.is_complete()
+        });
+        if !accepts {
+            out.push(sentence);
+        }
+    }
+    out
+}
@@ -2,13 +2,15 @@ use std::fmt::Display;
 
 use itertools::Itertools;
 
-use super::{Item, Token};
+use super::{Item, Range, Token};
 
 #[derive(Debug)]
 pub(super) enum Proof<'a, T> {
     Comp(&'a Item<T>, Box<Proof<'a, T>>, Box<Proof<'a, T>>),
     Pred(&'a Item<T>),
     Scan(&'a Item<T>, Box<Proof<'a, T>>),
+    /// an item for input that could not be parsed
+    Error(&'a Item<T>),
 }
 
 struct LatexProd<'a, T>(&'a str, &'a [Token<T>], &'a [Token<T>]);
@@ -70,6 +72,9 @@ where
                 )?;
                 writeln!(f, r"}}")?;
             }
+            Proof::Error(item) => {
+                writeln!(f, r"\axiominf{{ {} }}{{ error }}", LatexItem(item))?;
+            }
             Proof::Scan(item, mu) => {
                 writeln!(
                     f,
@@ -112,13 +117,17 @@ impl<T: Display> Display for FullProof<'_, T> {
 pub enum ParseTree<'a, T> {
     Terminal(&'a T),
     NonTerminal(&'a str, Vec<ParseTree<'a, T>>),
+    /// a nonterminal standing in for the input in the range, which could not be parsed
+    Error(&'a str, Range),
 }
 pub struct FullParseTree<'a, T>(pub(super) ParseTree<'a, T>);
 
 impl<'a, T> ParseTree<'a, T> {
     fn from_proof(proof: Proof<'a, T>, rule: &'a str, mut sub: Vec<ParseTree<'a, T>>) -> Self {
         match proof {
-            Proof::Pred(_) => todo!("not sure if i can do anything here"),
+            // only reachable for empty productions, there's nothing below
+            Proof::Pred(_) => ParseTree::NonTerminal(rule, sub),
+            Proof::Error(item) => ParseTree::Error(item.name(), item.range().clone()),
             Proof::Comp(item, mu, b) => {
                 let b_name = item.before().last().unwrap().nonterm();
 
@@ -148,9 +157,10 @@ impl<'a, T> ParseTree<'a, T> {
 impl<'a, T> From<Proof<'a, T>> for ParseTree<'a, T> {
     fn from(value: Proof<'a, T>) -> Self {
         match value {
-            Proof::Comp(item, _, _) | Proof::Pred(item) | Proof::Scan(item, _) => {
-                Self::from_proof(value, &item.name(), Vec::new())
-            }
+            Proof::Comp(item, _, _)
+            | Proof::Pred(item)
+            | Proof::Scan(item, _)
+            | Proof::Error(item) => Self::from_proof(value, item.name(), Vec::new()),
         }
     }
 }
@@ -169,6 +179,7 @@ impl<T: Display> Display for ParseTree<'_, T> {
                 write!(f, "[{rule}  {}]", children.iter().format(""))?;
                 Ok(())
             }
+            ParseTree::Error(rule, _) => write!(f, "[{rule} [{{error}}]]"),
         }
     }
 }
@@ -216,4 +227,3 @@ impl<T: Display> Display for Grammar<'_, T> {
         Ok(())
     }
 }
-
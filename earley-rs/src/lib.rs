@@ -1,15 +1,38 @@
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
     fmt::Display,
+    sync::Arc,
 };
 
 use table::Item;
 
 use self::latex::Proof;
 
+mod chunks;
+mod completion;
+#[cfg(feature = "serde")]
+pub mod de;
+mod document;
+mod forest;
+pub mod generate;
+pub mod grammar;
 pub mod latex;
+mod probability;
+mod recognizer;
+mod recovery;
+mod sentences;
 mod table;
+mod training;
+mod vocab;
+pub use chunks::Chunks;
+pub use document::Document;
+pub use probability::ProbabilityError;
+pub use recognizer::Recognizer;
+pub use recovery::{PanicMode, Recovered, Repair, RepairCosts, Synchronized};
 use table::Table;
+pub use training::{estimate_weights, Estimate};
+pub use vocab::Vocabulary;
 
 #[cfg(feature = "py")]
 pub mod python;
@@ -60,12 +83,15 @@ pub type Range = std::ops::Range<usize>;
 #[derive(Debug, Clone)]
 pub struct Grammar<T> {
     productions: HashMap<String, Vec<Vec<Token<T>>>>,
+    /// the weight of every production, in the same order
+    weights: HashMap<String, Vec<f64>>,
 }
 
 impl<T> Grammar<T> {
     pub fn new() -> Self {
         Self {
             productions: HashMap::new(),
+            weights: HashMap::new(),
         }
     }
 
@@ -73,14 +99,47 @@ impl<T> Grammar<T> {
         &mut self,
         nonterm: impl ToString,
         expansion: impl IntoIterator<Item = Token<T>>,
+    ) {
+        self.add_weighted_prod(nonterm, expansion, 1.0);
+    }
+
+    /// Like [Grammar::add_prod], but the production has a weight other than 1, e.g. its
+    /// probability. See [ParseInfo::best]
+    pub fn add_weighted_prod(
+        &mut self,
+        nonterm: impl ToString,
+        expansion: impl IntoIterator<Item = Token<T>>,
+        weight: f64,
     ) {
         let nonterm = nonterm.to_string();
         let expansion = expansion.into_iter().collect();
-        let entry = self.productions.entry(nonterm).or_default();
+        let entry = self.productions.entry(nonterm.clone()).or_default();
         entry.push(expansion);
+        self.weights.entry(nonterm).or_default().push(weight);
     }
 
-    pub fn latex(&self) -> latex::Grammar<T> {
+    /// The weight of `nonterm -> expansion`, if that is a production
+    pub fn weight(&self, nonterm: &str, expansion: &[Token<T>]) -> Option<f64>
+    where
+        T: PartialEq,
+    {
+        let idx = self
+            .productions
+            .get(nonterm)?
+            .iter()
+            .position(|x| x == expansion)?;
+        Some(self.weights[nonterm][idx])
+    }
+
+    /// the logarithm of the weight of `nonterm -> expansion`, if that is a production
+    pub(crate) fn log_weight(&self, nonterm: &str, expansion: &[Token<T>]) -> Option<f64>
+    where
+        T: PartialEq,
+    {
+        self.weight(nonterm, expansion).map(f64::ln)
+    }
+
+    pub fn latex(&self) -> latex::Grammar<'_, T> {
         latex::Grammar(self)
     }
 }
@@ -97,6 +156,8 @@ enum InsertedBy {
     Pred,
     Scan(Range),
     Comp(Range, Range),
+    /// stands in for input that could not be parsed, see [Parser::parse_panic_mode]
+    Error,
 }
 
 impl InsertedBy {
@@ -124,43 +185,259 @@ where
     I: Iterator<Item = T>,
     T: Clone + Eq + std::hash::Hash + Display,
 {
+    /// Prepares to parse `input`.
+    ///
+    /// `grammar` may also be an `Arc<Grammar<T>>`, so that many parsers share one grammar
     pub fn new(
         input: impl IntoIterator<IntoIter = I>,
-        grammar: Grammar<T>,
+        grammar: impl Into<Arc<Grammar<T>>>,
         initial: impl AsRef<str>,
+    ) -> Self {
+        Self::with_starts(input, grammar, [initial])
+    }
+
+    /// Like [Parser::new], but the input may be a string of any of `starts`.
+    ///
+    /// If it is a string of several of them, the first one wins, see [ParseInfo::start]
+    pub fn with_starts(
+        input: impl IntoIterator<IntoIter = I>,
+        grammar: impl Into<Arc<Grammar<T>>>,
+        starts: impl IntoIterator<Item = impl AsRef<str>>,
     ) -> Self {
         let input = input.into_iter();
-        let table = Table::new(grammar, initial, input.size_hint().0);
+        let table = Table::new(grammar, starts, input.size_hint().0);
         Self { input, table }
     }
-    pub fn parse(mut self) -> Result<ParseInfo<T>, Error> {
-        for token in self.input.by_ref() {
+    /// Parses the input, stopping at the first token that can't be accepted
+    pub fn parse(mut self) -> Result<ParseInfo<T>, SyntaxError<T>> {
+        for (position, token) in self.input.by_ref().enumerate() {
             // println!("{token}");
-            self.table.next(token)
+            self.table.next(token.clone());
+            if self.table.table.last().unwrap().is_empty() {
+                self.table.table.pop();
+                return Err(self.table.syntax_error(position, Some(token)));
+            }
         }
         // self.table.print_table();
 
-        let initial = &self.table.initial;
+        self.finish()
+    }
+
+    /// Like [Parser::parse], but keeps going after an error to find further errors.
+    ///
+    /// Tokens that can't be accepted are skipped.
+    pub fn collect_errors(mut self) -> Result<ParseInfo<T>, Vec<SyntaxError<T>>> {
+        let mut errors = Vec::new();
+        for (position, token) in self.input.by_ref().enumerate() {
+            self.table.next(token.clone());
+            if self.table.table.last().unwrap().is_empty() {
+                self.table.table.pop();
+                errors.push(self.table.syntax_error(position, Some(token)));
+            }
+        }
 
-        let _ = self
-            .table
-            .table
-            .last()
-            .unwrap()
-            .iter()
-            .find(|(item, _)| {
-                item.name() == initial
-                    && item.after().is_empty()
-                    && item.range() == &(0..self.table.table.len() - 1)
-            })
-            .ok_or(Error)?;
-        Ok(ParseInfo {
-            table: self.table.table,
-            initial: self.table.initial,
+        let position = self.table.table.len() - 1 + errors.len();
+        match self.finish() {
+            Ok(parse) if errors.is_empty() => Ok(parse),
+            Ok(_) => Err(errors),
+            Err(e) => {
+                errors.push(SyntaxError { position, ..e });
+                Err(errors)
+            }
+        }
+    }
+
+    /// Parses the longest prefix of the input that is complete.
+    ///
+    /// Stops at the first token that can't be accepted, the tokens after the prefix are
+    /// returned along with its parse.
+    pub fn parse_longest_prefix(mut self) -> Result<LongestPrefix<T, I>, SyntaxError<T>> {
+        let mut longest = self.table.is_complete().then_some(0);
+        // the tokens after the longest prefix so far
+        let mut pending = Vec::new();
+        let mut error = None;
+        for (position, token) in self.input.by_ref().enumerate() {
+            self.table.next(token.clone());
+            if self.table.table.last().unwrap().is_empty() {
+                self.table.table.pop();
+                error = Some(self.table.syntax_error(position, Some(token.clone())));
+                pending.push(token);
+                break;
+            }
+            pending.push(token);
+            if self.table.is_complete() {
+                longest = Some(position + 1);
+                pending.clear();
+            }
+        }
+
+        let Some(len) = longest else {
+            let position = self.table.table.len() - 1;
+            return Err(error.unwrap_or_else(|| self.table.syntax_error(position, None)));
+        };
+        self.table.table.truncate(len + 1);
+        Ok(LongestPrefix {
+            parse: self.table.into_parse().expect("the prefix is complete"),
+            len,
+            rest: pending.into_iter().chain(self.input),
         })
     }
+
+    fn finish(self) -> Result<ParseInfo<T>, SyntaxError<T>> {
+        if !self.table.is_complete() {
+            let position = self.table.table.len() - 1;
+            return Err(self.table.syntax_error(position, None));
+        }
+        Ok(self.table.into_parse().expect("table is complete"))
+    }
+}
+
+/// The result of [Parser::parse_longest_prefix]
+pub struct LongestPrefix<T, I> {
+    /// the parse of the prefix
+    pub parse: ParseInfo<T>,
+    /// the number of tokens in the prefix
+    pub len: usize,
+    /// the input after the prefix
+    pub rest: std::iter::Chain<std::vec::IntoIter<T>, I>,
 }
 
+/// Finds every non-empty span of `input` that derives `nonterminal`, ordered by start and end
+///
+/// ```
+/// # use earley::*;
+/// let mut grammar = Grammar::new();
+/// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+/// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+/// // x ( ) ( y ) ( ( ) )
+/// // 0 1 2 3 4 5 6 7 8 9
+/// let spans = find_all(grammar, "S", "x()(y)(())".chars());
+/// assert_eq!(spans, [1..3, 6..10, 7..9]);
+/// ```
+pub fn find_all<T>(
+    grammar: impl Into<Arc<Grammar<T>>>,
+    nonterminal: impl AsRef<str>,
+    input: impl IntoIterator<Item = T>,
+) -> Vec<Range>
+where
+    T: Clone + Eq + std::hash::Hash + Display,
+{
+    let input = input.into_iter();
+    let mut table = Table::new(grammar, [&nonterminal], input.size_hint().0).seed_everywhere();
+    for token in input {
+        table.next(token);
+    }
+
+    let mut out: Vec<Range> = table
+        .table
+        .iter()
+        .flat_map(|set| set.keys())
+        .filter(|item| item.name() == nonterminal.as_ref() && item.after().is_empty())
+        .map(|item| item.range().clone())
+        .filter(|range| !range.is_empty())
+        .collect();
+    out.sort_by_key(|range| (range.start, range.end));
+    out.dedup();
+    out
+}
+
+/// Parses every one of `inputs` with [Parser::parse], spread over as many threads as there
+/// are cores. The results are in the same order as `inputs`.
+///
+/// # Panics
+///
+/// Like parsing on a single thread, if the grammar has no productions for `initial` or for a
+/// nonterminal that is used in one of its productions.
+///
+/// # Examples
+///
+/// ```
+/// # use earley::*;
+/// let mut grammar = Grammar::new();
+/// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+/// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+/// let results = parse_many(grammar, "S", ["()", "(()", "(())"].map(|x| x.chars()));
+/// assert!(results[0].is_ok());
+/// assert_eq!(results[1].as_ref().err().unwrap().position, 3);
+/// assert!(results[2].is_ok());
+/// ```
+pub fn parse_many<T, I>(
+    grammar: impl Into<Arc<Grammar<T>>>,
+    initial: impl AsRef<str>,
+    inputs: impl IntoIterator<Item = I>,
+) -> Vec<Result<ParseInfo<T>, SyntaxError<T>>>
+where
+    I: IntoIterator<Item = T> + Send,
+    T: Clone + Eq + std::hash::Hash + Display + Send + Sync,
+{
+    let grammar = grammar.into();
+    let initial = initial.as_ref();
+    let inputs: Vec<I> = inputs.into_iter().collect();
+    let len = inputs.len();
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(len);
+    // the workers take the next input from here as soon as they are done with one
+    let inputs = std::sync::Mutex::new(inputs.into_iter().enumerate());
+
+    let mut results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut out = Vec::new();
+                    loop {
+                        let next = inputs.lock().unwrap().next();
+                        let Some((idx, input)) = next else {
+                            return out;
+                        };
+                        let parse = Parser::new(input, grammar.clone(), initial).parse();
+                        out.push((idx, parse));
+                    }
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| {
+                handle
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect()
+    });
+    results.sort_by_key(|(idx, _)| *idx);
+    debug_assert_eq!(results.len(), len);
+    results.into_iter().map(|(_, parse)| parse).collect()
+}
+
+/// A token that could not be accepted by a [Parser]
+#[derive(Debug, Clone)]
+pub struct SyntaxError<T> {
+    /// the index of the token in the input
+    pub position: usize,
+    /// the token, `None` if the input ended too early
+    pub token: Option<T>,
+    /// the terminals that could have been accepted instead
+    pub expected: HashSet<T>,
+}
+
+impl<T: Display> Display for SyntaxError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.token {
+            Some(token) => write!(f, "unexpected `{token}` at {}", self.position)?,
+            None => write!(f, "unexpected end of input at {}", self.position)?,
+        }
+        if !self.expected.is_empty() {
+            let mut expected: Vec<_> = self.expected.iter().map(|t| format!("`{t}`")).collect();
+            expected.sort();
+            write!(f, ", expected one of {}", expected.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: std::fmt::Debug + Display> std::error::Error for SyntaxError<T> {}
+
 /// Prefix Parser, can incrementally parse a string
 ///
 /// - Use [PrefixParser::try_next] to advance the parser by a single token
@@ -210,8 +487,16 @@ where
 ///
 /// assert!(parser.finish().is_ok()); // we now have a fully parsed string
 /// ```
+#[derive(Clone)]
 pub struct PrefixParser<T> {
     table: Table<T>,
+    /// the terminals [PrefixParser::try_next_inserting] may insert, with their costs
+    insertable: Vec<(T, usize)>,
+    /// how many terminals may be inserted before a single token
+    max_insertions: usize,
+    /// the forward and inner probabilities of the items of every set, if they are kept track of,
+    /// see [PrefixParser::with_probabilities]
+    probabilities: Option<probability::Tracking<T>>,
 }
 
 impl<T> PrefixParser<T>
@@ -219,9 +504,87 @@ where
     T: Clone + Eq + std::hash::Hash + Display,
 {
     /// constructs a new [PrefixParser] for the given grammar, with the initial state
-    pub fn new(grammar: Grammar<T>, initial: impl AsRef<str>) -> Self {
-        let table = Table::new(grammar, initial, 0);
-        Self { table }
+    pub fn new(grammar: impl Into<Arc<Grammar<T>>>, initial: impl AsRef<str>) -> Self {
+        Self::with_starts(grammar, [initial])
+    }
+
+    /// Like [PrefixParser::new], but the tokens may form a string of any of `starts`
+    pub fn with_starts(
+        grammar: impl Into<Arc<Grammar<T>>>,
+        starts: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Self {
+        Self {
+            table: Table::new(grammar, starts, 0),
+            insertable: Vec::new(),
+            max_insertions: 0,
+            probabilities: None,
+        }
+    }
+
+    /// Sets the terminals [PrefixParser::try_next_inserting] may insert, with their costs, and
+    /// how many of them it may insert before a single token. Insertions that cost `usize::MAX`
+    /// in total are never made
+    pub fn set_insertable(&mut self, terminals: impl IntoIterator<Item = (T, usize)>, max: usize) {
+        self.insertable = terminals.into_iter().collect();
+        self.max_insertions = max;
+    }
+
+    /// Like [PrefixParser::try_next], but if `token` can't be accepted, first inserts the
+    /// cheapest sequence of insertable terminals after which it can.
+    ///
+    /// Returns the inserted terminals, the state is unchanged if there is no such sequence. See
+    /// [PrefixParser::set_insertable]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// grammar.add_prod("P", [Token::NonTerm("S".into()), Token::Term('.')]);
+    /// let mut parser = PrefixParser::new(grammar, "P");
+    /// parser.set_insertable([(')', 1)], 2);
+    /// parser.try_extend("((".chars()).unwrap();
+    /// assert_eq!(parser.try_next_inserting('.').unwrap(), [')', ')']);
+    /// assert!(parser.is_complete());
+    /// ```
+    pub fn try_next_inserting(&mut self, token: T) -> Result<Vec<T>, Error> {
+        if self.try_next(token.clone()).is_ok() {
+            return Ok(Vec::new());
+        }
+        // uniform cost search over the sequences of insertions, the earlier one wins on ties
+        let mut states = vec![(Vec::new(), self.fork())];
+        let mut queue = BinaryHeap::from([Reverse((0usize, 0))]);
+        while let Some(Reverse((cost, idx))) = queue.pop() {
+            let (inserted, parser) = states[idx].clone();
+            if !inserted.is_empty() {
+                let mut parser = parser.fork();
+                if parser.try_next(token.clone()).is_ok() {
+                    *self = parser;
+                    return Ok(inserted);
+                }
+            }
+            if inserted.len() == self.max_insertions {
+                continue;
+            }
+            let legal = parser.legal_tokens();
+            for (terminal, terminal_cost) in &self.insertable {
+                let cost = cost.saturating_add(*terminal_cost);
+                if cost == usize::MAX || !legal.contains(terminal) {
+                    continue;
+                }
+                let mut parser = parser.fork();
+                if parser.try_next(terminal.clone()).is_err() {
+                    continue;
+                }
+                let mut inserted = inserted.clone();
+                inserted.push(terminal.clone());
+                states.push((inserted, parser));
+                queue.push(Reverse((cost, states.len() - 1)));
+            }
+        }
+        Err(Error)
     }
 
     /// Attempts to advance the state
@@ -231,38 +594,215 @@ where
             self.table.table.pop();
             return Err(Error);
         }
+        if let Some(probabilities) = &mut self.probabilities {
+            probabilities.push(&self.table.grammar, self.table.table.last().unwrap());
+        }
         Ok(())
     }
 
-    pub fn finish(&self) -> Result<ParseInfo<T>, Error> {
-        let initial = &self.table.initial;
+    /// Attempts to advance the state by all of `tokens`.
+    ///
+    /// If any of them cannot be accepted, the state is left as it was before the call
+    pub fn try_extend(&mut self, tokens: impl IntoIterator<Item = T>) -> Result<(), Error> {
+        let checkpoint = self.checkpoint();
+        for token in tokens {
+            if let Err(e) = self.try_next(token) {
+                self.rollback(&checkpoint)
+                    .expect("checkpoint is before the current state");
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
 
-        let _ = self
-            .table
-            .table
-            .last()
-            .unwrap()
-            .iter()
-            .find(|(item, _)| {
-                item.name() == initial
-                    && item.after().is_empty()
-                    && item.range() == &(0..self.table.table.len() - 1)
-            })
-            .ok_or(Error)?;
+    /// Whether [PrefixParser::try_extend] would succeed with `tokens`, without changing the state
+    pub fn accepts(&self, tokens: &[T]) -> bool {
+        self.fork().try_extend(tokens.iter().cloned()).is_ok()
+    }
 
-        Ok(ParseInfo {
-            table: self.table.table.clone(),
-            initial: self.table.initial.clone(),
-        })
+    /// Whether the tokens accepted so far form a complete string, i.e. whether
+    /// [PrefixParser::finish] would succeed. Does not allocate
+    pub fn is_complete(&self) -> bool {
+        self.table.is_complete()
+    }
+
+    pub fn finish(&self) -> Result<ParseInfo<T>, Error> {
+        self.table.clone().into_parse().ok_or(Error)
+    }
+
+    /// Like [PrefixParser::finish], but consumes the parser instead of copying its state
+    pub fn into_parse(self) -> Result<ParseInfo<T>, Error> {
+        self.table.into_parse().ok_or(Error)
     }
 
     pub fn legal_tokens(&self) -> HashSet<T> {
         self.table.legal_tokens()
     }
+
+    /// A shortest sequence of tokens that makes [PrefixParser::finish] succeed
+    ///
+    /// Returns `None` if there is no way to complete the parse, which can only happen if the
+    /// grammar contains nonterminals that don't derive any string.
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// let mut parser = PrefixParser::new(grammar, "S");
+    /// parser.try_extend("((".chars()).unwrap();
+    /// assert_eq!(parser.shortest_completion(), Some(vec![')', ')']));
+    /// ```
+    pub fn shortest_completion(&self) -> Option<Vec<T>> {
+        self.table.shortest_completion()
+    }
+
+    /// All sequences of at most `max_len` tokens that make [PrefixParser::finish] succeed,
+    /// ordered by length.
+    ///
+    /// Note that there may be exponentially many of them
+    pub fn completions(&self, max_len: usize) -> Vec<Vec<T>> {
+        let mut out = Vec::new();
+        let mut level = vec![(Vec::new(), self.fork())];
+        for len in 0..=max_len {
+            out.extend(
+                level
+                    .iter()
+                    .filter(|(_, parser)| parser.is_complete())
+                    .map(|(completion, _)| completion.clone()),
+            );
+            if len == max_len {
+                break;
+            }
+            let mut next_level = Vec::new();
+            for (completion, parser) in level {
+                for token in parser.legal_tokens() {
+                    let mut parser = parser.fork();
+                    if parser.try_next(token.clone()).is_err() {
+                        continue;
+                    }
+                    let mut completion = completion.clone();
+                    completion.push(token);
+                    next_level.push((completion, parser));
+                }
+            }
+            level = next_level;
+        }
+        out
+    }
+
+    /// Creates an independent copy of the parser.
+    ///
+    /// This is cheap, the Earley sets parsed so far are shared between the parsers and only the
+    /// sets for tokens accepted after forking are owned by each of them.
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// let mut parser = PrefixParser::new(grammar, "S");
+    /// parser.try_next('(').unwrap();
+    ///
+    /// let mut speculative = parser.fork();
+    /// speculative.try_next(')').unwrap();
+    /// assert!(speculative.finish().is_ok());
+    ///
+    /// // the original parser is unaffected
+    /// assert!(parser.finish().is_err());
+    /// ```
+    pub fn fork(&self) -> Self {
+        self.clone()
+    }
+
+    /// Removes the last accepted token, fails if there is none
+    pub fn undo(&mut self) -> Result<(), Error> {
+        if self.table.table.len() == 1 {
+            return Err(Error);
+        }
+        self.table.table.pop();
+        if let Some(probabilities) = &mut self.probabilities {
+            probabilities.truncate(self.table.table.len());
+        }
+        Ok(())
+    }
+
+    /// Remembers the current state, which can be restored using [PrefixParser::rollback]
+    pub fn checkpoint(&self) -> Checkpoint<T> {
+        Checkpoint {
+            len: self.table.table.len(),
+            set: self.table.table.last().unwrap().clone(),
+        }
+    }
+
+    /// Restores the state at `checkpoint`, forgetting all tokens accepted since.
+    ///
+    /// Fails if the state at `checkpoint` was already undone, also if tokens were accepted again
+    /// since, or if `checkpoint` is from a parser this one wasn't forked from
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// let mut parser = PrefixParser::new(grammar, "S");
+    /// parser.try_next('(').unwrap();
+    /// let checkpoint = parser.checkpoint();
+    /// parser.try_next(')').unwrap();
+    /// assert!(parser.finish().is_ok());
+    ///
+    /// parser.rollback(&checkpoint).unwrap();
+    /// // currently parsed: "("
+    /// assert!(parser.finish().is_err());
+    /// ```
+    pub fn rollback(&mut self, checkpoint: &Checkpoint<T>) -> Result<(), Error> {
+        match self.table.table.get(checkpoint.len - 1) {
+            Some(set) if Arc::ptr_eq(set, &checkpoint.set) => {}
+            _ => return Err(Error),
+        }
+        self.table.table.truncate(checkpoint.len);
+        if let Some(probabilities) = &mut self.probabilities {
+            probabilities.truncate(checkpoint.len);
+        }
+        Ok(())
+    }
+}
+
+/// A state of a [PrefixParser], see [PrefixParser::checkpoint]
+///
+/// The last Earley set of the state is kept, a parser only has the same state if it still has
+/// that very set
+#[derive(Clone)]
+pub struct Checkpoint<T> {
+    /// the number of sets
+    len: usize,
+    set: Arc<HashMap<Item<T>, InsertedBy>>,
+}
+
+impl<T> Checkpoint<T> {
+    /// the number of tokens accepted before the checkpoint
+    pub fn tokens(&self) -> usize {
+        self.len - 1
+    }
+}
+
+impl<T> PartialEq for Checkpoint<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && Arc::ptr_eq(&self.set, &other.set)
+    }
+}
+
+impl<T> Eq for Checkpoint<T> {}
+
+impl<T> std::fmt::Debug for Checkpoint<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("tokens", &self.tokens())
+            .finish_non_exhaustive()
+    }
 }
 
 pub struct ParseInfo<T> {
-    table: Vec<HashMap<Item<T>, InsertedBy>>,
+    table: Vec<Arc<HashMap<Item<T>, InsertedBy>>>,
+    grammar: Arc<Grammar<T>>,
     initial: String,
 }
 
@@ -280,6 +820,9 @@ where
         root: &'a Item<T>,
         inserted_by: &InsertedBy,
     ) -> latex::Proof<'a, T> {
+        if let InsertedBy::Error = inserted_by {
+            return Proof::Error(root);
+        }
         match root.before().last() {
             None => {
                 assert!(matches!(inserted_by, InsertedBy::Pred));
@@ -322,19 +865,33 @@ where
         }
     }
 
-    pub fn reconstruct(&self) -> latex::FullProof<T> {
+    /// the complete items of the start symbol that span the whole input
+    fn roots(&self) -> impl Iterator<Item = (&Item<T>, &InsertedBy)> {
         let initial: &str = self.initial.as_ref();
+        self.table.last().unwrap().iter().filter(move |(item, _)| {
+            item.name() == initial && item.after().is_empty() && item.range().start == 0
+        })
+    }
+
+    pub fn reconstruct(&self) -> latex::FullProof<'_, T> {
         let root = self
-            .table
-            .last()
-            .unwrap()
-            .iter()
-            .find(|(item, _)| item.name() == initial && item.after().is_empty())
+            .roots()
+            .next()
             .expect("parse had failed. if you see this you may complain about this horrid api");
 
         let proof = self.reconstruct_tree(self.table.len() - 1, root.0, root.1);
         latex::FullProof(proof)
     }
+
+    /// the start symbol the input is a string of
+    pub fn start(&self) -> &str {
+        &self.initial
+    }
+
+    /// the parse tree of the input, see [ParseInfo::reconstruct] for the full proof
+    pub fn tree(&self) -> latex::ParseTree<'_, T> {
+        self.reconstruct().0.into()
+    }
 }
 
 #[cfg(test)]
@@ -404,6 +961,18 @@ mod tests {
         grammar
     }
 
+    /// S ::= A "x"
+    /// A ::= \eps | "a"
+    ///
+    /// the nullable A has to be completed before the first token
+    fn nullable_prefix() -> Grammar<String> {
+        let mut grammar = Grammar::new();
+        grammar.add_prod(S, [nt("A"), t("x")]);
+        grammar.add_prod("A", []);
+        grammar.add_prod("A", [t("a")]);
+        grammar
+    }
+
     fn input(x: &str) -> impl Iterator<Item = String> + '_ {
         x.chars()
             .filter(|x| !x.is_whitespace())
@@ -458,6 +1027,10 @@ mod tests {
     test_grammar!(FAIL palindrome_easy_fail, palindrome, "abaa");
     test_grammar!(FAIL palindrome_easy_fail2, palindrome, "ab");
 
+    test_grammar!(nullable_prefix_empty, nullable_prefix, "x");
+    test_grammar!(nullable_prefix_easy, nullable_prefix, "ax");
+    test_grammar!(FAIL nullable_prefix_easy_fail, nullable_prefix, "");
+
     test_grammar!(well_formed_parentheses1, well_formed_parentheses, "()");
     test_grammar!(
         well_formed_parentheses2,
@@ -466,4 +1039,1101 @@ mod tests {
     );
     // test_grammar!(FAIL well_formed_parentheses_fail1, well_formed_parentheses, "((((())))))");
     test_grammar!(FAIL well_formed_parentheses_fail2, well_formed_parentheses, "((((())))");
+
+    fn strings<const N: usize>(xs: [&str; N]) -> HashSet<String> {
+        xs.into_iter().map(String::from).collect()
+    }
+
+    /// a linear congruential generator of numbers in `0.0..1.0`, the same ones for every `seed`
+    fn lcg(mut seed: u64) -> impl FnMut() -> f64 {
+        move || {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 11) as f64 / (1u64 << 53) as f64
+        }
+    }
+
+    #[test]
+    fn syntax_error_position() {
+        let parser = Parser::new(input("2 + * 4 + +"), factored_arith(), "P");
+        let error = parser.parse().err().unwrap();
+        assert_eq!((error.position, error.token.as_deref()), (2, Some("*")));
+        assert_eq!(error.expected, strings(["1", "2", "3", "4"]));
+        assert_eq!(
+            error.to_string(),
+            "unexpected `*` at 2, expected one of `1`, `2`, `3`, `4`"
+        );
+
+        let parser = Parser::new(input("2 + 3 *"), factored_arith(), "P");
+        let error = parser.parse().err().unwrap();
+        assert_eq!((error.position, error.token), (4, None));
+    }
+
+    #[test]
+    fn longest_prefix() {
+        let parser = Parser::new(input("1+2*+3"), factored_arith(), "P");
+        let prefix = parser.parse_longest_prefix().unwrap();
+        assert_eq!(prefix.len, 3);
+        assert_eq!(prefix.rest.collect::<String>(), "*+3");
+        assert_eq!(
+            prefix.parse.tree().to_string(),
+            "[P  [S  [S  [M  [T  [{1}]]]][{+}][M  [T  [{2}]]]]]"
+        );
+
+        // the whole input
+        let parser = Parser::new(input("1+2"), factored_arith(), "P");
+        let prefix = parser.parse_longest_prefix().unwrap();
+        assert_eq!(prefix.len, 3);
+        assert_eq!(prefix.rest.count(), 0);
+
+        let parser = Parser::new(input("aab"), improper_rev(), S);
+        let prefix = parser.parse_longest_prefix().unwrap();
+        assert_eq!(prefix.len, 2);
+        assert_eq!(prefix.rest.collect::<String>(), "b");
+
+        // the empty prefix
+        let parser = Parser::new(input("ba"), improper_rev(), S);
+        let prefix = parser.parse_longest_prefix().unwrap();
+        assert_eq!(prefix.len, 0);
+        assert_eq!(prefix.rest.collect::<String>(), "ba");
+
+        let parser = Parser::new(input("+1"), factored_arith(), "P");
+        let error = parser.parse_longest_prefix().err().unwrap();
+        assert_eq!((error.position, error.token.as_deref()), (0, Some("+")));
+
+        let parser = Parser::new(input("1+"), factored_arith(), "P").parse_longest_prefix();
+        assert_eq!(parser.unwrap().rest.collect::<String>(), "+");
+    }
+
+    #[test]
+    fn find_all_in_noise() {
+        let spans = find_all(factored_arith(), "T", input("x1+2yy3*4+"));
+        assert_eq!(spans, [1..2, 3..4, 6..7, 8..9]);
+
+        let spans = find_all(factored_arith(), "M", input("x1+2yy3*4+"));
+        assert_eq!(spans, [1..2, 3..4, 6..7, 6..9, 8..9]);
+
+        let spans = find_all(factored_arith(), "S", input("1+2"));
+        assert_eq!(spans, [0..1, 0..3, 2..3]);
+
+        assert!(find_all(factored_arith(), "S", input("+*")).is_empty());
+
+        // a match behind a nullable prefix is found at the start as well as later on
+        let spans = find_all(nullable_prefix(), S, input("xbx"));
+        assert_eq!(spans, [0..1, 2..3]);
+    }
+
+    #[test]
+    fn start_symbols() {
+        let starts = ["T", "M", "S"];
+        for (x, start) in [("2", "T"), ("2*3", "M"), ("2+3*4", "S")] {
+            let parse = Parser::with_starts(input(x), factored_arith(), starts).parse();
+            assert_eq!(parse.unwrap().start(), start);
+        }
+        assert!(Parser::with_starts(input("2+"), factored_arith(), starts)
+            .parse()
+            .is_err());
+
+        // a sub-rule on its own
+        assert!(Parser::new(input("2*3"), factored_arith(), "M")
+            .parse()
+            .is_ok());
+        assert!(Parser::new(input("2+3"), factored_arith(), "M")
+            .parse()
+            .is_err());
+
+        let mut parser = PrefixParser::with_starts(factored_arith(), ["T", "S"]);
+        parser.try_extend(input("1+")).unwrap();
+        assert_eq!(parser.shortest_completion().unwrap().len(), 1);
+        parser.try_next("2".into()).unwrap();
+        assert_eq!(parser.finish().unwrap().start(), "S");
+    }
+
+    #[test]
+    fn shared_grammar() {
+        let grammar = Arc::new(factored_arith());
+        let parser = PrefixParser::new(grammar.clone(), "P");
+        let parse = Parser::new(input("1+2"), grammar.clone(), "P").parse();
+        assert!(parse.is_ok());
+        assert_eq!(find_all(grammar.clone(), "T", input("1+2")).len(), 2);
+        // the parse refers to the grammar as well
+        assert_eq!(Arc::strong_count(&grammar), 3);
+        drop(parse);
+        assert_eq!(Arc::strong_count(&grammar), 2);
+        drop(parser);
+        assert_eq!(Arc::strong_count(&grammar), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "grammar must contain nonterminal")]
+    fn parse_many_missing_nonterminal() {
+        let mut grammar = Grammar::new();
+        grammar.add_prod(S, [t("a"), nt("B")]);
+        parse_many(grammar, S, [input("ab")]);
+    }
+
+    #[test]
+    fn parse_many_in_order() {
+        fn send_sync<T: Send + Sync>() {}
+        send_sync::<Grammar<String>>();
+        send_sync::<ParseInfo<String>>();
+        send_sync::<SyntaxError<String>>();
+        send_sync::<PrefixParser<String>>();
+
+        let inputs: Vec<String> = (0..200)
+            .map(|i| match i % 3 {
+                0 => vec!["1+2*3"; i % 7 + 1].join("+"),
+                1 => format!("{}+", i % 4 + 1),
+                _ => (i % 4 + 1).to_string(),
+            })
+            .collect();
+        let results = parse_many(factored_arith(), "P", inputs.iter().map(|x| input(x)));
+        assert_eq!(results.len(), inputs.len());
+        for (x, result) in inputs.iter().zip(results) {
+            let expected = Parser::new(input(x), factored_arith(), "P").parse();
+            match (result, expected) {
+                (Ok(_), Ok(_)) => {}
+                (Err(a), Err(b)) => assert_eq!(a.position, b.position),
+                (a, b) => panic!("{x}: {:?} vs {:?}", a.is_ok(), b.is_ok()),
+            }
+        }
+
+        assert!(parse_many(factored_arith(), "P", Vec::<Vec<String>>::new()).is_empty());
+    }
+
+    #[test]
+    fn recovery() {
+        let costs = RepairCosts::new();
+        let recover = |x: &str, costs: &RepairCosts<String>| {
+            Parser::new(input(x), factored_arith(), "P")
+                .parse_with_recovery(costs)
+                .unwrap()
+        };
+
+        let recovered = recover("1+2*3", &costs);
+        assert_eq!((recovered.cost, recovered.repairs), (0, vec![]));
+
+        let recovered = recover("1+*2", &costs);
+        assert_eq!(recovered.cost, 1);
+        assert!(Parser::new(recovered.tokens, factored_arith(), "P")
+            .parse()
+            .is_ok());
+
+        let recovered = recover("1+x", &costs);
+        assert_eq!(recovered.cost, 1);
+        assert!(matches!(
+            &recovered.repairs[..],
+            [Repair::Substitute { position: 2, found, .. }] if found == "x"
+        ));
+
+        let recovered = recover("", &costs);
+        assert_eq!(recovered.cost, 1);
+        assert_eq!(recovered.tokens.len(), 1);
+
+        // with empty productions
+        let recovered = Parser::new(input("abba ab"), improper_rev(), S)
+            .parse_with_recovery(&costs)
+            .unwrap();
+        assert_eq!(recovered.cost, 2);
+        assert!(Parser::new(recovered.tokens, improper_rev(), S)
+            .parse()
+            .is_ok());
+        let recovered = Parser::new(input("x"), nullable_prefix(), S)
+            .parse_with_recovery(&costs)
+            .unwrap();
+        assert_eq!((recovered.cost, recovered.repairs), (0, vec![]));
+
+        // operators are made more expensive than dropping a number
+        let costs = costs.insert("+".into(), 5).insert("*".into(), 5);
+        let recovered = recover("12", &costs);
+        assert_eq!(recovered.cost, 1);
+        assert!(matches!(&recovered.repairs[..], [Repair::Delete { .. }]));
+
+        let costs = costs
+            .delete("1".into(), usize::MAX)
+            .delete("2".into(), usize::MAX)
+            .substitute("+".into(), 5)
+            .substitute("*".into(), 5);
+        let recovered = recover("12", &costs);
+        assert_eq!(recovered.cost, 5);
+        assert!(matches!(
+            &recovered.repairs[..],
+            [Repair::Insert { position: 1, .. }]
+        ));
+
+        // costs that add up to more than usize::MAX are forbidden
+        let mut grammar = Grammar::new();
+        grammar.add_prod("P", [nt("T"), t("+"), nt("T")]);
+        grammar.add_prod("T", [t("1")]);
+        let grammar = Arc::new(grammar);
+        let costs = RepairCosts::new()
+            .insert("1".to_string(), usize::MAX / 2 + 1)
+            .insert("+".into(), usize::MAX)
+            .delete("+".into(), usize::MAX)
+            .substitute("1".into(), usize::MAX)
+            .substitute("+".into(), usize::MAX);
+        let parser = Parser::new(input("+"), grammar.clone(), "P");
+        assert!(parser.parse_with_recovery(&costs).is_err());
+        let costs = costs.insert("1".into(), usize::MAX / 2 - 1);
+        let recovered = Parser::new(input("+"), grammar, "P")
+            .parse_with_recovery(&costs)
+            .unwrap();
+        assert_eq!(recovered.cost, usize::MAX - 3);
+    }
+
+    #[test]
+    fn panic_mode() {
+        // stats ::= stats stat ";" | stat ";"
+        // stat ::= "x" "=" "1" | "d" stats "e"
+        let mut grammar = Grammar::new();
+        grammar.add_prod("stats", [nt("stats"), nt("stat"), t(";")]);
+        grammar.add_prod("stats", [nt("stat"), t(";")]);
+        grammar.add_prod("stat", [t("x"), t("="), t("1")]);
+        grammar.add_prod("stat", [t("d"), nt("stats"), t("e")]);
+        let grammar = Arc::new(grammar);
+        let mode = PanicMode::new(strings([";", "e"]), ["stat"]);
+        let parse =
+            |x: &str| Parser::new(input(x), grammar.clone(), "stats").parse_panic_mode(&mode);
+
+        let synchronized = parse("x=1; d x=1; e;").unwrap();
+        assert!(synchronized.errors.is_empty());
+
+        let synchronized = parse("x=1; x1; d x=1; = ; e; x=;").unwrap();
+        let errors: Vec<_> = synchronized.errors.iter().map(|e| e.position).collect();
+        assert_eq!(errors, [5, 12, 18]);
+        fn error_spans<'a>(tree: &'a latex::ParseTree<'a, String>, out: &mut Vec<Range>) {
+            match tree {
+                latex::ParseTree::Terminal(_) => {}
+                latex::ParseTree::NonTerminal(_, children) => {
+                    children.iter().for_each(|child| error_spans(child, out))
+                }
+                latex::ParseTree::Error(name, range) => {
+                    assert_eq!(*name, "stat");
+                    out.push(range.clone());
+                }
+            }
+        }
+        let mut spans = Vec::new();
+        error_spans(&synchronized.parse.tree(), &mut spans);
+        assert_eq!(spans, [4..6, 12..13, 16..18]);
+
+        // an empty error where a statement is missing keeps the statements around it
+        let synchronized = parse("x=1;;x=1;").unwrap();
+        let errors: Vec<_> = synchronized.errors.iter().map(|e| e.position).collect();
+        assert_eq!(errors, [4]);
+        let mut spans = Vec::new();
+        error_spans(&synchronized.parse.tree(), &mut spans);
+        assert_eq!((spans.len(), &spans[0]), (1, &(4..4)));
+
+        // there is no ";" to synchronize on at the end
+        let errors = parse("x=1; x=").err().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].position, errors[0].token.as_ref()), (6, None));
+
+        // stat ::= "x" "=" expr ";"
+        // expr ::= "1" | "(" expr ")"
+        let mut grammar = Grammar::new();
+        grammar.add_prod("stat", [t("x"), t("="), nt("expr"), t(";")]);
+        grammar.add_prod("expr", [t("1")]);
+        grammar.add_prod("expr", [t("("), nt("expr"), t(")")]);
+        let mode = PanicMode::new(strings([")", ";"]), ["expr"]);
+        // the error at ! ends before the first ), the second ) could only be recovered from by
+        // an error starting before the first one
+        let errors = Parser::new(input("x=(!));"), grammar, "stat")
+            .parse_panic_mode(&mode)
+            .err()
+            .unwrap();
+        let errors: Vec<_> = errors.iter().map(|e| e.position).collect();
+        assert_eq!(errors, [3, 5]);
+
+        // an empty S would derive itself through S -> S S, so there is no empty error for it
+        let mode = PanicMode::new(strings([")"]), [S]);
+        let errors = Parser::new(input(""), well_formed_parentheses(), S)
+            .parse_panic_mode(&mode)
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!((errors[0].position, errors[0].token.as_ref()), (0, None));
+    }
+
+    #[test]
+    fn chunks() {
+        let chunks = Parser::new(input("1+2 ) 3*4 + *"), factored_arith(), "P").parse_chunks();
+        let spans: Vec<_> = chunks.spans().collect();
+        assert_eq!(spans, [("P", &(0..3)), ("P", &(4..7))]);
+        assert_eq!(
+            chunks.trees()[1].to_string(),
+            Parser::new(input("3*4"), factored_arith(), "P")
+                .parse()
+                .unwrap()
+                .tree()
+                .to_string()
+        );
+
+        // the whole input is a single chunk
+        let chunks = Parser::new(input("1+2*3"), factored_arith(), "P").parse_chunks();
+        assert_eq!(chunks.spans().collect::<Vec<_>>(), [("P", &(0..5))]);
+
+        // sub-constituents are found where the start symbol can't be
+        let chunks = Parser::new(input("+2*3 x"), factored_arith(), "M").parse_chunks();
+        assert_eq!(chunks.spans().collect::<Vec<_>>(), [("M", &(1..4))]);
+
+        let chunks = Parser::new(input("x"), factored_arith(), "P").parse_chunks();
+        assert_eq!(chunks.spans().count(), 0);
+    }
+
+    #[test]
+    fn k_best() {
+        // the number of ways to split n pairs of parentheses in a row is a catalan number
+        for (x, trees) in [
+            ("()", 1),
+            ("()()()", 2),
+            ("()()()()", 5),
+            ("(()())()()()", 5),
+        ] {
+            let parse = Parser::new(input(x), well_formed_parentheses(), S)
+                .parse()
+                .unwrap();
+            let derivations = parse.k_best(100);
+            assert_eq!(derivations.len(), trees);
+            let distinct: HashSet<String> = derivations.iter().map(|x| x.0.to_string()).collect();
+            assert_eq!(distinct.len(), trees);
+        }
+
+        // attaching the prepositional phrase to the verb or to the noun
+        let mut grammar = Grammar::new();
+        grammar.add_weighted_prod("VP", [t("v"), nt("NP"), nt("PP")], 0.3);
+        grammar.add_weighted_prod("VP", [t("v"), nt("NP")], 0.7);
+        grammar.add_weighted_prod("NP", [nt("NP"), nt("PP")], 0.2);
+        grammar.add_weighted_prod("NP", [t("n")], 0.8);
+        grammar.add_prod("PP", [t("p"), nt("NP")]);
+        let parse = Parser::new(input("vnpn"), grammar, "VP").parse().unwrap();
+
+        let (tree, score) = parse.best();
+        assert_eq!(
+            tree.to_string(),
+            "[VP  [{v}][NP  [{n}]][PP  [{p}][NP  [{n}]]]]"
+        );
+        assert!((score - (0.3f64 * 0.8 * 0.8).ln()).abs() < 1e-9);
+
+        let derivations = parse.k_best(3);
+        assert_eq!(derivations.len(), 2);
+        let tree = "[VP  [{v}][NP  [NP  [{n}]][PP  [{p}][NP  [{n}]]]]]";
+        assert_eq!(derivations[1].0.to_string(), tree);
+        assert!((derivations[1].1 - (0.7f64 * 0.2 * 0.8 * 0.8).ln()).abs() < 1e-9);
+
+        // with a cycle there are infinitely many derivations
+        let mut grammar = Grammar::new();
+        grammar.add_weighted_prod("A", [nt("B")], 0.5);
+        grammar.add_prod("A", [t("x")]);
+        grammar.add_prod("B", [nt("A")]);
+        let parse = Parser::new(input("x"), grammar, "A").parse().unwrap();
+        let scores: Vec<f64> = parse.k_best(3).into_iter().map(|x| x.1.exp()).collect();
+        assert_eq!(scores, [1.0, 0.5, 0.25]);
+
+        // a cycle that costs nothing, through empty S
+        let mut grammar = Grammar::new();
+        grammar.add_prod(S, [nt(S), nt(S)]);
+        grammar.add_prod(S, []);
+        grammar.add_prod(S, [t("a")]);
+        let parse = Parser::new(input("a"), grammar, S).parse().unwrap();
+        let derivations = parse.k_best(3);
+        assert_eq!(derivations.len(), 3);
+        assert!(derivations.iter().all(|x| x.1 == 0.0));
+        let distinct: HashSet<String> = derivations.iter().map(|x| x.0.to_string()).collect();
+        assert_eq!(distinct.len(), 3);
+    }
+
+    #[test]
+    fn generate() {
+        use crate::generate::Generator;
+        use crate::latex::ParseTree;
+        fn depth(tree: &ParseTree<'_, String>) -> usize {
+            match tree {
+                ParseTree::NonTerminal(_, children) => {
+                    1 + children.iter().map(depth).max().unwrap_or(0)
+                }
+                _ => 0,
+            }
+        }
+
+        let mut rng = lcg(7);
+        for (grammar, start) in [
+            (factored_arith(), "P"),
+            (palindrome(), S),
+            (well_formed_parentheses(), S),
+        ] {
+            let generator = Generator::new(&grammar, 12, 15);
+            let mut lengths = HashSet::new();
+            for _ in 0..50 {
+                let tree = generator.generate_tree(start, &mut rng).unwrap();
+                assert!(depth(&tree) <= 12);
+                let sentence = generator.generate(start, &mut rng).unwrap();
+                assert!(sentence.len() <= 15);
+                lengths.insert(sentence.len());
+                let parse = Parser::new(sentence, grammar.clone(), start).parse();
+                assert!(parse.is_ok());
+            }
+            assert!(lengths.len() > 1);
+        }
+
+        // the shortest sentence of factored_arith is 4 deep
+        let grammar = factored_arith();
+        assert!(Generator::new(&grammar, 3, 10)
+            .generate("P", &mut rng)
+            .is_none());
+        let generator = Generator::new(&grammar, 4, 1);
+        assert_eq!(generator.generate("P", &mut rng).unwrap().len(), 1);
+        assert!(Generator::new(&grammar, 10, 0)
+            .generate("P", &mut rng)
+            .is_none());
+        assert!(generator.generate("Q", &mut rng).is_none());
+    }
+
+    #[test]
+    fn sentences() {
+        let grammar = palindrome();
+        let sentences: Vec<String> = grammar.sentences(S, 3).map(|x| x.concat()).collect();
+        let expected = ["", "a", "b", "aa", "bb", "aaa", "aba", "bab", "bbb"];
+        assert_eq!(sentences, expected);
+        for n in 0..6 {
+            assert_eq!(grammar.count_sentences(S, n), Some(1 << n.div_ceil(2)));
+        }
+
+        // every sentence is accepted, and all accepted sentences are there
+        let grammar = factored_arith();
+        let sentences: HashSet<String> = grammar.sentences("P", 5).map(|x| x.concat()).collect();
+        assert_eq!(sentences.len(), 4 + 4 * 2 * 4 + 4 * 2 * 4 * 2 * 4);
+        for x in ["1", "2*3", "1+2*3", "4+4+4"] {
+            assert!(sentences.contains(x));
+        }
+        assert!(!sentences.contains("1+") && !sentences.contains("1+2+3+4"));
+        for n in 0..6 {
+            let count = sentences.iter().filter(|x| x.len() == n).count();
+            assert_eq!(grammar.count_sentences("P", n), Some(count as u128));
+        }
+
+        // infinitely many derivations
+        let mut grammar = Grammar::new();
+        grammar.add_prod("A", [nt("B")]);
+        grammar.add_prod("A", [t("x")]);
+        grammar.add_prod("B", [nt("A")]);
+        grammar.add_prod("C", [t("x"), t("x")]);
+        assert_eq!(grammar.sentences("A", 3).count(), 1);
+        assert_eq!(grammar.count_sentences("A", 0), Some(0));
+        assert_eq!(grammar.count_sentences("A", 1), None);
+        assert_eq!(grammar.count_sentences("C", 2), Some(1));
+        assert_eq!(grammar.count_sentences("D", 2), Some(0));
+
+        let mut rng = lcg(3);
+        assert_eq!(grammar.sample_sentence("A", 1, &mut rng), None);
+        assert_eq!(grammar.sample_sentence("C", 3, &mut rng), None);
+
+        // all 32 sentences of 3 tokens are about equally likely
+        let grammar = factored_arith();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for _ in 0..1600 {
+            let sentence = grammar.sample_sentence("P", 3, &mut rng).unwrap();
+            *seen.entry(sentence.concat()).or_default() += 1;
+        }
+        assert_eq!(seen.len(), 2 * 4 * 4);
+        assert!(seen.values().all(|&x| (25..=75).contains(&x)));
+    }
+
+    #[test]
+    fn compare_grammars() {
+        use crate::grammar::{compare, Difference};
+
+        // right-recursive instead of left-recursive
+        let mut refactored = Grammar::new();
+        refactored.add_prod("P", [nt("S")]);
+        refactored.add_prod("S", [nt("M"), t('+'), nt("S")]);
+        refactored.add_prod("S", [nt("M")]);
+        refactored.add_prod("M", [nt("T"), t('*'), nt("M")]);
+        refactored.add_prod("M", [nt("T")]);
+        for x in ['1', '2', '3', '4'] {
+            refactored.add_prod("T", [t(x)]);
+        }
+        assert!(compare(&factored_arith(), &refactored, "P", 5).is_empty());
+
+        // forgot a terminal
+        refactored.productions.get_mut("T").unwrap().pop();
+        let differences = compare(&factored_arith(), &refactored, "P", 5);
+        assert_eq!(differences, [Difference::OnlyA(vec!["4".to_string()])]);
+
+        // the shortest differences only
+        let mut wrong = factored_arith();
+        wrong.add_prod("M", [nt("M"), t('/'), nt("T")]);
+        wrong.add_prod("S", [nt("S"), t('-'), nt("M"), t('-')]);
+        let differences = compare(&factored_arith(), &wrong, "P", 5);
+        assert_eq!(differences.len(), 16);
+        assert!(differences
+            .iter()
+            .all(|x| matches!(x, Difference::OnlyB(x) if x[1] == "/")));
+        assert_eq!(differences[0].sentence(), ["1", "/", "1"]);
+
+        // neither derives anything from a nonterminal without productions
+        assert_eq!(compare(&palindrome(), &palindrome(), "P", 3), []);
+        let differences = compare(&palindrome(), &Grammar::new(), S, 3);
+        assert_eq!(differences, [Difference::OnlyA(vec![])]);
+    }
+
+    #[test]
+    fn estimate_weights() {
+        // unambiguous, so the estimate is the relative frequency of the productions
+        let corpus = ["1+2*3", "1", "1*2"];
+        let estimate =
+            super::estimate_weights(factored_arith(), "P", corpus.map(input), 3).unwrap();
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        let sum = [nt("S"), t("+"), nt("M")];
+        assert!(close(
+            estimate.grammar.weight("S", &sum).unwrap(),
+            1.0 / 4.0
+        ));
+        assert!(close(
+            estimate.grammar.weight("T", &[t("1")]).unwrap(),
+            1.0 / 2.0
+        ));
+        assert_eq!(estimate.grammar.weight("T", &[t("4")]), Some(0.0));
+        let lls = &estimate.log_likelihoods;
+        assert_eq!(lls.len(), 3);
+        assert!(close(lls[1], lls[2]) && lls[0] < lls[1]);
+
+        // the likelihood never decreases
+        let mut grammar = Grammar::new();
+        grammar.add_prod("S", [nt("S"), nt("S")]);
+        grammar.add_prod("S", [nt("S"), nt("S"), nt("S")]);
+        grammar.add_prod("S", [t("a")]);
+        let corpus = ["aaa", "aaaa", "a"];
+        let estimate = super::estimate_weights(grammar, "S", corpus.map(input), 10);
+        let lls = estimate.ok().unwrap().log_likelihoods;
+        assert!(lls.windows(2).all(|x| x[0] <= x[1] + 1e-12));
+        assert!(lls[0] < lls[9]);
+
+        // U never appears in a parse, its weights stay as they are
+        let mut grammar = Grammar::new();
+        grammar.add_prod("S", [t("a")]);
+        grammar.add_prod("S", [t("b"), nt("U")]);
+        grammar.add_weighted_prod("U", [t("c")], 0.0);
+        grammar.add_weighted_prod("U", [t("d")], 0.0);
+        let estimate = super::estimate_weights(grammar, "S", ["a"].map(input), 2).unwrap();
+        assert_eq!(estimate.grammar.weight("S", &[t("a")]), Some(1.0));
+        assert_eq!(estimate.grammar.weight("U", &[t("c")]), Some(0.0));
+        assert_eq!(estimate.grammar.weight("U", &[t("d")]), Some(0.0));
+
+        let corpus = ["1+2", "1+", "+1"];
+        let err = super::estimate_weights(factored_arith(), "P", corpus.map(input), 1);
+        assert_eq!(err.err().unwrap().0, 1);
+    }
+
+    #[test]
+    fn prefix_probability() {
+        // S = T (+ T)*, with another + after every T with probability 0.4
+        let mut grammar = Grammar::new();
+        grammar.add_weighted_prod("S", [nt("S"), t("+"), nt("T")], 0.4);
+        grammar.add_weighted_prod("S", [nt("T")], 0.6);
+        grammar.add_weighted_prod("T", [t("x")], 0.5);
+        grammar.add_weighted_prod("T", [t("("), nt("S"), t(")")], 0.5);
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        let mut parser = PrefixParser::with_probabilities(grammar.clone(), "S").unwrap();
+        assert_eq!(parser.prefix_probability(), Some(1.0));
+        let next = parser.next_token_distribution().unwrap();
+        assert_eq!(next.len(), 2);
+        assert!(close(next["x"], 0.5) && close(next["("], 0.5));
+
+        parser.try_next("x".to_string()).unwrap();
+        assert!(close(parser.prefix_probability().unwrap(), 0.5));
+        let next = parser.next_token_distribution().unwrap();
+        assert_eq!(next.len(), 1);
+        assert!(close(next["+"], 0.4));
+
+        let checkpoint = parser.checkpoint();
+        parser.try_extend(input("+(x")).unwrap();
+        assert!(close(
+            parser.prefix_probability().unwrap(),
+            0.5 * 0.4 * 0.5 * 0.5
+        ));
+        let next = parser.next_token_distribution().unwrap();
+        assert!(close(next["+"], 0.4) && close(next[")"], 0.6));
+
+        // undone tokens don't count
+        parser.rollback(&checkpoint).unwrap();
+        assert!(close(parser.prefix_probability().unwrap(), 0.5));
+        parser.try_next("+".to_string()).unwrap();
+        parser.undo().unwrap();
+        assert!(close(parser.next_token_distribution().unwrap()["+"], 0.4));
+        assert!(parser.try_next(")".to_string()).is_err());
+        assert!(close(parser.prefix_probability().unwrap(), 0.5));
+
+        // only kept track of when asked for
+        let parser = PrefixParser::new(grammar.clone(), "S");
+        assert_eq!(parser.prefix_probability(), None);
+        let parser = PrefixParser::with_starts(grammar.clone(), ["S", "T"]);
+        assert_eq!(parser.next_token_distribution(), None);
+
+        // a prefix that can't occur
+        grammar.add_weighted_prod("T", [t("y")], 0.0);
+        let mut parser = PrefixParser::with_probabilities(grammar, "S").unwrap();
+        parser.try_next("y".to_string()).unwrap();
+        assert_eq!(parser.prefix_probability(), Some(0.0));
+        assert_eq!(parser.next_token_distribution(), None);
+    }
+
+    #[test]
+    fn prefix_probability_improper() {
+        let parser = PrefixParser::with_probabilities(factored_arith(), "P");
+        assert!(matches!(
+            parser.err(),
+            Some(ProbabilityError::NotNormalized(_))
+        ));
+
+        let mut grammar = Grammar::new();
+        grammar.add_weighted_prod("S", [t("a"), nt("S")], 0.5);
+        grammar.add_weighted_prod("S", [], 0.5);
+        let parser = PrefixParser::with_probabilities(grammar, "S");
+        assert!(matches!(parser.err(), Some(ProbabilityError::EmptyProduction(x)) if x == "S"));
+
+        // S -> S a never ends
+        let mut grammar = Grammar::new();
+        grammar.add_weighted_prod("S", [nt("S"), t("a")], 1.0);
+        let parser = PrefixParser::with_probabilities(grammar, "S");
+        assert!(matches!(parser.err(), Some(ProbabilityError::Singular)));
+    }
+
+    #[test]
+    fn syntax_error_collect() {
+        let parser = Parser::new(input("1 + * 2 * * 3"), factored_arith(), "P");
+        let errors = parser.collect_errors().err().unwrap();
+        let positions: Vec<_> = errors
+            .iter()
+            .map(|e| (e.position, e.token.clone()))
+            .collect();
+        assert_eq!(
+            positions,
+            [(2, Some("*".to_owned())), (5, Some("*".to_owned()))]
+        );
+
+        let parser = Parser::new(input("1 + + 2 *"), factored_arith(), "P");
+        let errors = parser.collect_errors().err().unwrap();
+        let positions: Vec<_> = errors
+            .iter()
+            .map(|e| (e.position, e.token.clone()))
+            .collect();
+        assert_eq!(positions, [(2, Some("+".to_owned())), (5, None)]);
+        assert_eq!(errors[1].expected, strings(["1", "2", "3", "4"]));
+
+        let parser = Parser::new(input("1 + 2"), factored_arith(), "P");
+        assert!(parser.collect_errors().is_ok());
+    }
+
+    #[test]
+    fn prefix_parser_undo() {
+        let mut parser = PrefixParser::new(well_formed_parentheses(), S);
+        assert!(parser.undo().is_err());
+
+        for token in input("(()") {
+            parser.try_next(token).unwrap();
+        }
+        assert!(parser.finish().is_err());
+        parser.undo().unwrap();
+        // "(("
+        assert_eq!(
+            parser.legal_tokens(),
+            HashSet::from(["(".into(), ")".into()])
+        );
+        parser.undo().unwrap();
+        parser.try_next(")".into()).unwrap();
+        // "()"
+        assert!(parser.finish().is_ok());
+    }
+
+    #[test]
+    fn prefix_parser_rollback() {
+        let mut parser = PrefixParser::new(well_formed_parentheses(), S);
+        let empty = parser.checkpoint();
+        parser.try_next("(".into()).unwrap();
+        let open = parser.checkpoint();
+        parser.try_next(")".into()).unwrap();
+        assert!(parser.finish().is_ok());
+
+        parser.rollback(&open).unwrap();
+        assert!(parser.finish().is_err());
+        assert!(parser.try_next(")".into()).is_ok());
+
+        parser.rollback(&empty).unwrap();
+        assert!(parser.try_next(")".into()).is_err());
+        // `open` is no longer reachable
+        assert!(parser.rollback(&open).is_err());
+
+        // "(" again, but not the state `open` was taken in
+        parser.try_next("(".into()).unwrap();
+        assert!(parser.rollback(&open).is_err());
+        assert_eq!(parser.checkpoint().tokens(), open.tokens());
+
+        // forks share the states from before forking
+        let mut fork = parser.fork();
+        let reopened = parser.checkpoint();
+        fork.try_next(")".into()).unwrap();
+        fork.rollback(&reopened).unwrap();
+        assert!(fork.finish().is_err());
+
+        // a parser for the same grammar in the same state, but a different one
+        let mut other = PrefixParser::new(well_formed_parentheses(), S);
+        other.try_next("(".into()).unwrap();
+        assert!(other.rollback(&reopened).is_err());
+        assert!(other.rollback(&empty).is_err());
+    }
+
+    #[test]
+    fn prefix_parser_fork() {
+        let mut parser = PrefixParser::new(well_formed_parentheses(), S);
+        for token in input("(()") {
+            parser.try_next(token).unwrap();
+        }
+
+        let mut fork = parser.fork();
+        assert!(Arc::ptr_eq(&parser.table.table[2], &fork.table.table[2]));
+        fork.try_next(")".into()).unwrap();
+        assert!(fork.finish().is_ok());
+        fork.undo().unwrap();
+        fork.undo().unwrap();
+        fork.try_next("(".into()).unwrap();
+
+        // the forks don't affect each other
+        assert!(parser.finish().is_err());
+        parser.try_next(")".into()).unwrap();
+        assert!(parser.finish().is_ok());
+        assert!(fork.finish().is_err());
+    }
+
+    #[test]
+    fn prefix_parser_try_extend() {
+        let mut parser = PrefixParser::new(factored_arith(), "P");
+        parser.try_extend(input("1+2")).unwrap();
+        assert!(parser.try_extend(input("*3+*4")).is_err());
+        // nothing of the failed sequence was accepted
+        assert!(parser.finish().is_ok());
+        assert!(parser.try_next("*".into()).is_ok());
+        assert!(parser.finish().is_err());
+        parser.try_extend(input("3+4")).unwrap();
+        assert!(parser.finish().is_ok());
+    }
+
+    #[test]
+    fn prefix_parser_accepts() {
+        let mut parser = PrefixParser::new(factored_arith(), "P");
+        parser.try_extend(input("1+")).unwrap();
+        let before = parser.checkpoint();
+        let tokens = |x| input(x).collect::<Vec<_>>();
+        assert!(parser.accepts(&tokens("2*3")));
+        assert!(parser.accepts(&tokens("")));
+        assert!(!parser.accepts(&tokens("2**")));
+        assert!(!parser.accepts(&tokens("+")));
+        // the state is unchanged, "1+" is still incomplete
+        assert!(parser.finish().is_err());
+        assert_eq!(parser.checkpoint(), before);
+        assert_eq!(before.tokens(), 2);
+    }
+
+    #[test]
+    fn prefix_parser_shortest_completion() {
+        let mut parser = PrefixParser::new(factored_arith(), "P");
+        assert_eq!(parser.shortest_completion().unwrap().len(), 1);
+        parser.try_extend(input("1+2*")).unwrap();
+        assert_eq!(parser.shortest_completion().unwrap().len(), 1);
+        parser.try_next("3".into()).unwrap();
+        assert_eq!(parser.shortest_completion(), Some(vec![]));
+
+        let mut parser = PrefixParser::new(well_formed_parentheses(), S);
+        parser.try_extend(input("((()((")).unwrap();
+        let completion = parser.shortest_completion().unwrap();
+        assert_eq!(completion, input("))))").collect::<Vec<_>>());
+        parser.try_extend(completion).unwrap();
+        assert!(parser.finish().is_ok());
+    }
+
+    #[test]
+    fn prefix_parser_shortest_completion_empty() {
+        let mut parser = PrefixParser::new(palindrome(), S);
+        parser.try_extend(input("abb")).unwrap();
+        assert_eq!(parser.shortest_completion(), Some(input("a").collect()));
+        parser.try_next("b".into()).unwrap();
+        assert_eq!(parser.shortest_completion(), Some(input("a").collect()));
+        parser.try_next("a".into()).unwrap();
+        assert_eq!(parser.shortest_completion(), Some(vec![]));
+
+        // the completion is accepted even if it starts behind a nullable nonterminal
+        let mut parser = PrefixParser::new(nullable_prefix(), S);
+        let completion = parser.shortest_completion().unwrap();
+        assert_eq!(completion, ["x"]);
+        for token in completion {
+            parser.try_next(token).unwrap();
+        }
+        assert!(parser.is_complete());
+    }
+
+    #[test]
+    fn prefix_parser_completions() {
+        let mut parser = PrefixParser::new(well_formed_parentheses(), S);
+        parser.try_next("(".into()).unwrap();
+        let completions: HashSet<String> = parser
+            .completions(3)
+            .into_iter()
+            .map(|x| x.concat())
+            .collect();
+        assert_eq!(
+            completions,
+            HashSet::from([")".into(), "())".into(), ")()".into()])
+        );
+        assert!(parser.completions(0).is_empty());
+    }
+
+    #[test]
+    fn prefix_parser_is_complete() {
+        let mut parser = PrefixParser::new(improper_rev(), S);
+        assert!(parser.is_complete());
+        parser.try_extend(input("ab")).unwrap();
+        assert!(!parser.is_complete());
+        assert!(parser.finish().is_err());
+        parser.try_extend(input("ba")).unwrap();
+        assert!(parser.is_complete());
+
+        let parse = parser.into_parse().unwrap();
+        assert_eq!(parse.table.len(), 5);
+    }
+
+    #[test]
+    fn prefix_parser_insertions() {
+        // P ::= B "."
+        // B ::= B St | St
+        // St ::= "x" ";" | "(" B ")" ";"
+        let mut grammar = Grammar::new();
+        grammar.add_prod("P", [nt("B"), t(".")]);
+        grammar.add_prod("B", [nt("B"), nt("St")]);
+        grammar.add_prod("B", [nt("St")]);
+        grammar.add_prod("St", [t("x"), t(";")]);
+        grammar.add_prod("St", [t("("), nt("B"), t(")"), t(";")]);
+        let grammar = Arc::new(grammar);
+
+        let mut parser = PrefixParser::new(grammar.clone(), "P");
+        parser.set_insertable([(";".into(), 1), (")".into(), 2)], 2);
+        assert!(parser.try_next_inserting("x".into()).unwrap().is_empty());
+        assert_eq!(parser.try_next_inserting("x".into()).unwrap(), [";"]);
+        parser.try_extend(input("; (x")).unwrap();
+
+        // three insertions are needed
+        let before = parser.checkpoint();
+        assert!(parser.try_next_inserting(".".into()).is_err());
+        assert_eq!(parser.checkpoint(), before);
+        assert_eq!(before.tokens(), 6);
+
+        assert!(parser.try_next_inserting(";".into()).unwrap().is_empty());
+        assert_eq!(parser.try_next_inserting(".".into()).unwrap(), [")", ";"]);
+        assert!(parser.is_complete());
+
+        let mut parser = PrefixParser::new(grammar.clone(), "P");
+        parser.set_insertable([(";".into(), 1), (")".into(), 2)], 3);
+        parser.try_extend(input("(x")).unwrap();
+        let inserted = parser.try_next_inserting(".".into()).unwrap();
+        assert_eq!(inserted, [";", ")", ";"]);
+
+        // costs that add up to usize::MAX are forbidden
+        let mut parser = PrefixParser::new(grammar, "P");
+        parser.set_insertable([(";".into(), usize::MAX / 2 + 1), (")".into(), 1)], 3);
+        parser.try_extend(input("(x")).unwrap();
+        assert!(parser.try_next_inserting(".".into()).is_err());
+        assert!(parser.try_next_inserting(";".into()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reconstruct_whole_input() {
+        // the last set also has complete items of the initial nonterminal for the suffixes
+        for _ in 0..20 {
+            let parse = Parser::new(input("()()()"), well_formed_parentheses(), S)
+                .parse()
+                .unwrap();
+            let tree = parse.tree().to_string();
+            assert_eq!(tree.matches('(').count(), 3, "{tree}");
+        }
+    }
+
+    #[test]
+    fn vocabulary_mask() {
+        let entries = ["1", "1+", "+", "*2", "12", "1+2*", "+*", ""];
+        let vocabulary = Vocabulary::new(entries.map(input));
+        assert_eq!(vocabulary.len(), entries.len());
+
+        let mut parser = PrefixParser::new(factored_arith(), "P");
+        let allowed = |parser: &PrefixParser<String>| {
+            let mask = vocabulary.mask(parser);
+            entries
+                .iter()
+                .zip(mask)
+                .filter(|(_, allowed)| *allowed)
+                .map(|(entry, _)| *entry)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(allowed(&parser), ["1", "1+", "1+2*", ""]);
+
+        parser.try_next("3".into()).unwrap();
+        assert_eq!(allowed(&parser), ["+", "*2", ""]);
+        // the parser is unchanged
+        assert!(parser.is_complete());
+    }
+
+    fn assert_same_chart<T: Eq + std::hash::Hash + std::fmt::Debug>(a: &Table<T>, b: &Table<T>) {
+        assert_eq!(a.table.len(), b.table.len());
+        for (a, b) in a.table.iter().zip(&b.table) {
+            let a: HashSet<_> = a.keys().collect();
+            let b: HashSet<_> = b.keys().collect();
+            assert_eq!(a, b);
+        }
+    }
+
+    #[test]
+    fn document_edit() {
+        let mut document = Document::new(input("1+2*3+4*4*2+1"), factored_arith(), "P");
+        assert!(document.is_complete());
+
+        // same length
+        let recomputed = document.edit(2..3, input("4"));
+        assert!(recomputed < 3);
+        let fresh = Document::new(input("1+4*3+4*4*2+1"), factored_arith(), "P");
+        assert_same_chart(&document.table, &fresh.table);
+
+        // shorter
+        let recomputed = document.edit(1..5, input(""));
+        assert!(recomputed < 3);
+        let fresh = Document::new(input("1+4*4*2+1"), factored_arith(), "P");
+        assert_same_chart(&document.table, &fresh.table);
+        assert!(document.parse().is_ok());
+
+        // longer, and invalid
+        document.edit(2..2, input("++"));
+        let fresh = Document::new(input("1+++4*4*2+1"), factored_arith(), "P");
+        assert_same_chart(&document.table, &fresh.table);
+        assert!(document.parse().is_err());
+
+        // and valid again
+        document.edit(1..3, input("*3"));
+        let fresh = Document::new(input("1*3+4*4*2+1"), factored_arith(), "P");
+        assert_same_chart(&document.table, &fresh.table);
+        assert!(document.is_complete());
+    }
+
+    #[test]
+    fn document_edit_nested() {
+        let mut document = Document::new(input("(()(()))()"), well_formed_parentheses(), S);
+        for (range, tokens, expected) in [
+            (4..4, "()", "(()(()()))()"),
+            (0..1, "", "()(()()))()"),
+            (11..11, ")", "()(()()))())"),
+            (0..0, "((", "((()(()()))())"),
+            (5..14, "", "((()("),
+            (5..5, ")))", "((()()))"),
+        ] {
+            document.edit(range, input(tokens));
+            let fresh = Document::new(input(expected), well_formed_parentheses(), S);
+            assert_same_chart(&document.table, &fresh.table);
+            assert_eq!(document.is_complete(), fresh.is_complete());
+        }
+    }
+
+    #[test]
+    fn document_random_edits() {
+        let mut rng = lcg(0x2545f4914f6cdd1d);
+        let mut random = |n: usize| (rng() * n as f64) as usize;
+        let mut text = String::from("(()(()))()((()))");
+        let mut document = Document::new(input(&text), well_formed_parentheses(), S);
+        for _ in 0..200 {
+            let start = random(text.len() + 1);
+            let end = start + random(text.len() - start + 1).min(3);
+            let replacement: String = (0..random(4))
+                .map(|_| if random(2) == 0 { '(' } else { ')' })
+                .collect();
+            text.replace_range(start..end, &replacement);
+            document.edit(start..end, input(&replacement));
+
+            let fresh = Document::new(input(&text), well_formed_parentheses(), S);
+            assert_same_chart(&document.table, &fresh.table);
+        }
+    }
+
+    #[test]
+    fn recognizer_agrees_with_parser() {
+        type Case = (
+            fn() -> Grammar<String>,
+            &'static str,
+            &'static [&'static str],
+        );
+        let cases: [Case; 5] = [
+            (
+                factored_arith,
+                "P",
+                &["2+3*4", "2+*4", "", "1+2+3+4*3*2*1+1", "1+"],
+            ),
+            (improper_rev, S, &["", "aabbaa", "aabaa", "ab", "abba"]),
+            (nullable_prefix, S, &["x", "ax", "", "a", "aax", "xx"]),
+            (palindrome, S, &["", "aabbaa", "aabaa", "abaa", "ab", "b"]),
+            (
+                well_formed_parentheses,
+                S,
+                &["()", "(((((())))))", "((((())))", ")("],
+            ),
+        ];
+        for (grammar, initial, inputs) in cases {
+            for x in inputs {
+                let expected = Parser::new(input(x), grammar(), initial).parse().is_ok();
+                let recognizer = Recognizer::new(&grammar(), initial);
+                assert_eq!(recognizer.recognize(input(x)), expected, "{x:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn recognizer_drops_sets() {
+        // L ::= L "a" | "b"
+        let mut grammar = Grammar::new();
+        grammar.add_prod("L", [nt("L"), t("a")]);
+        grammar.add_prod("L", [t("b")]);
+
+        let mut recognizer = Recognizer::new(&grammar, "L");
+        assert!(!recognizer.is_complete());
+        recognizer.try_next("b".into()).unwrap();
+        for _ in 0..100 {
+            assert!(recognizer.is_complete());
+            recognizer.try_next("a".into()).unwrap();
+            assert!(recognizer.live_sets() <= 2);
+        }
+        assert_eq!(recognizer.legal_tokens(), HashSet::from(["a".into()]));
+        assert!(recognizer.try_next("b".into()).is_err());
+
+        // parentheses can't forget where they were opened
+        let mut recognizer = Recognizer::new(&well_formed_parentheses(), S);
+        for token in input("((((") {
+            recognizer.try_next(token).unwrap();
+        }
+        assert_eq!(recognizer.live_sets(), 5);
+    }
+
+    #[test]
+    fn recognizer_long_input() {
+        // stats ::= stats stat ";" | stat ";"
+        // stat ::= "x" "=" "1"
+        let mut grammar = Grammar::new();
+        grammar.add_prod("stats", [nt("stats"), nt("stat"), t(";")]);
+        grammar.add_prod("stats", [nt("stat"), t(";")]);
+        grammar.add_prod("stat", [t("x"), t("="), t("1")]);
+
+        let mut recognizer = Recognizer::new(&grammar, "stats");
+        for _ in 0..20_000 {
+            for token in ["x", "=", "1", ";"] {
+                recognizer.try_next(token.into()).unwrap();
+                assert!(recognizer.live_sets() <= 5);
+            }
+        }
+        assert!(recognizer.is_complete());
+    }
 }
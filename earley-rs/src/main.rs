@@ -1,4 +1,4 @@
-use std::{fs::File, process::Command};
+use std::{fs::File, process::Command, sync::Arc};
 
 use earley::{latex::FullParseTree, Grammar, Parser, PrefixParser, Token};
 
@@ -35,6 +35,7 @@ fn tmp() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[allow(unreachable_code)]
 fn main() -> anyhow::Result<()> {
     tmp()?;
     panic!();
@@ -64,7 +65,8 @@ fn main() -> anyhow::Result<()> {
     grammar.add_prod("digit", [t('9')]);
     grammar.add_prod("digit", [t('0')]);
 
-    let parser = Parser::new("((((())))))".chars(), grammar.to_owned(), "S");
+    let grammar = Arc::new(grammar);
+    let parser = Parser::new("((((())))))".chars(), grammar.clone(), "S");
     let parse_result = parser.parse()?;
 
     let proof = parse_result.reconstruct();
@@ -0,0 +1,370 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Display,
+    hash::Hash,
+    sync::Arc,
+};
+
+use crate::{table::Item, Grammar, InsertedBy, PrefixParser, Token};
+
+/// The forward and inner probability of an item, following Stolcke, "An Efficient
+/// Probabilistic Context-Free Parsing Algorithm that Computes Prefix Probabilities" (1995).
+///
+/// The forward probability is the sum of the probabilities of all derivations of the input so
+/// far that use the item, the inner probability only counts the part of those derivations below
+/// the item
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Probability {
+    forward: f64,
+    inner: f64,
+}
+
+/// The probabilities of the items of one Earley set
+type Probabilities<T> = Arc<HashMap<Item<T>, Probability>>;
+
+/// Why the weights of a grammar can't be taken to be probabilities, see
+/// [PrefixParser::with_probabilities]
+#[derive(Debug, thiserror::Error)]
+pub enum ProbabilityError {
+    #[error("the weights of the productions of {0} are not probabilities that sum to 1")]
+    NotNormalized(String),
+    #[error("{0} has an empty production")]
+    EmptyProduction(String),
+    #[error("the leftmost derivations of some nonterminal never end")]
+    Singular,
+}
+
+/// The probabilities of the items of every set so far
+#[derive(Clone)]
+pub(crate) struct Tracking<T> {
+    closures: Arc<Closures>,
+    sets: Vec<Probabilities<T>>,
+}
+
+impl<T> Tracking<T>
+where
+    T: Clone + Eq + Hash,
+{
+    fn new(grammar: &Grammar<T>, initial: &str) -> Result<Self, ProbabilityError> {
+        let closures = Closures::new(grammar)?;
+        let initial = initial_probabilities(grammar, &closures, initial);
+        Ok(Self {
+            closures: Arc::new(closures),
+            sets: vec![Arc::new(initial)],
+        })
+    }
+
+    /// adds the probabilities of the newest set `chart`
+    pub(crate) fn push(&mut self, grammar: &Grammar<T>, chart: &HashMap<Item<T>, InsertedBy>) {
+        let next = next_probabilities(grammar, &self.closures, &self.sets, chart);
+        self.sets.push(Arc::new(next));
+    }
+
+    /// keeps the probabilities of the first `len` sets
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.sets.truncate(len);
+    }
+}
+
+/// The closures of the left-corner and unit relations of a grammar, whose weights are taken to be
+/// probabilities.
+///
+/// `left_corner[X][Y]` is the probability of deriving `Y ...` from `X` by only expanding the
+/// leftmost symbol, and `unit[X][Y]` that of deriving `Y` from `X`. Both include the empty
+/// derivation.
+#[derive(Debug)]
+struct Closures {
+    index: HashMap<String, usize>,
+    left_corner: Vec<Vec<f64>>,
+    unit: Vec<Vec<f64>>,
+}
+
+impl Closures {
+    /// Fails if the weights are not probabilities, or if there are empty productions, which
+    /// the closures don't account for
+    fn new<T>(grammar: &Grammar<T>) -> Result<Self, ProbabilityError> {
+        for (name, productions) in &grammar.productions {
+            if productions.iter().any(|production| production.is_empty()) {
+                return Err(ProbabilityError::EmptyProduction(name.clone()));
+            }
+            let weights = &grammar.weights[name];
+            let sum: f64 = weights.iter().sum();
+            if weights.iter().any(|&w| !(0.0..=1.0).contains(&w)) || (sum - 1.0).abs() > 1e-9 {
+                return Err(ProbabilityError::NotNormalized(name.clone()));
+            }
+        }
+
+        let mut names: Vec<&String> = grammar.productions.keys().collect();
+        names.sort();
+        let index: HashMap<String, usize> = names
+            .iter()
+            .enumerate()
+            .map(|(i, &name)| (name.clone(), i))
+            .collect();
+
+        let n = index.len();
+        let mut left_corner = vec![vec![0.0; n]; n];
+        let mut unit = vec![vec![0.0; n]; n];
+        for (name, productions) in &grammar.productions {
+            let x = index[name];
+            for (production, weight) in productions.iter().zip(&grammar.weights[name]) {
+                let Some(y) = production.first().and_then(|first| match first {
+                    Token::NonTerm(nt) => index.get(nt),
+                    Token::Term(_) => None,
+                }) else {
+                    continue;
+                };
+                let y = *y;
+                left_corner[x][y] += weight;
+                if production.len() == 1 {
+                    unit[x][y] += weight;
+                }
+            }
+        }
+        Ok(Self {
+            index,
+            left_corner: closure(left_corner).ok_or(ProbabilityError::Singular)?,
+            unit: closure(unit).ok_or(ProbabilityError::Singular)?,
+        })
+    }
+}
+
+/// `(I - m)^-1`, the sum of all powers of `m`, by gauss-jordan elimination. `None` if `I - m`
+/// is singular, then the sum doesn't converge
+fn closure(m: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = m.len();
+    let mut a: Vec<Vec<f64>> = m
+        .into_iter()
+        .enumerate()
+        .map(|(i, row)| {
+            row.into_iter()
+                .enumerate()
+                .map(|(j, x)| (i == j) as u8 as f64 - x)
+                .collect()
+        })
+        .collect();
+    let mut out: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| (i == j) as u8 as f64).collect())
+        .collect();
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))
+            .expect("there is a row left");
+        a.swap(col, pivot);
+        out.swap(col, pivot);
+        let p = a[col][col];
+        if p.abs() < 1e-12 {
+            return None;
+        }
+        for j in 0..n {
+            a[col][j] /= p;
+            out[col][j] /= p;
+        }
+        for row in 0..n {
+            let factor = a[row][col];
+            if row == col || factor == 0.0 {
+                continue;
+            }
+            for j in 0..n {
+                a[row][j] -= factor * a[col][j];
+                out[row][j] -= factor * out[col][j];
+            }
+        }
+    }
+    out.iter().flatten().all(|x| x.is_finite()).then_some(out)
+}
+
+/// the probabilities of the items in set 0
+fn initial_probabilities<T>(
+    grammar: &Grammar<T>,
+    closures: &Closures,
+    initial: &str,
+) -> HashMap<Item<T>, Probability>
+where
+    T: Clone + Eq + Hash,
+{
+    let mut out = HashMap::new();
+    let start = Probability {
+        forward: 1.0,
+        inner: 1.0,
+    };
+    predict(grammar, closures, 0, initial, start, &mut out);
+    out
+}
+
+/// adds the items predicted by an item with probability `from`, expecting `expected`
+fn predict<T>(
+    grammar: &Grammar<T>,
+    closures: &Closures,
+    j: usize,
+    expected: &str,
+    from: Probability,
+    out: &mut HashMap<Item<T>, Probability>,
+) where
+    T: Clone + Eq + Hash,
+{
+    // nonterminals without productions predict nothing
+    let Some(&x) = closures.index.get(expected) else {
+        return;
+    };
+    let row = &closures.left_corner[x];
+    for (name, productions) in &grammar.productions {
+        let r = row[closures.index[name]];
+        if r == 0.0 {
+            continue;
+        }
+        for (production, &weight) in productions.iter().zip(&grammar.weights[name]) {
+            let item = Item::init(name.clone(), production.clone(), j..j);
+            let p = out.entry(item).or_default();
+            p.forward += from.forward * r * weight;
+            p.inner = weight;
+        }
+    }
+}
+
+/// The probabilities of the items in the newest set `chart`, given those of all sets before it.
+///
+/// Completion goes through chains of unit productions in one step, using [Closures::unit], so
+/// complete items of unit productions are not completed any further. The grammar is assumed to
+/// have no empty productions, so a complete item only depends on complete items that start after
+/// it.
+fn next_probabilities<T>(
+    grammar: &Grammar<T>,
+    closures: &Closures,
+    sets: &[Probabilities<T>],
+    chart: &HashMap<Item<T>, InsertedBy>,
+) -> HashMap<Item<T>, Probability>
+where
+    T: Clone + Eq + Hash,
+{
+    let j = sets.len();
+    let mut out: HashMap<Item<T>, Probability> = HashMap::new();
+    // the complete items to complete with, by where they start
+    let mut todo: BTreeMap<usize, Vec<Item<T>>> = BTreeMap::new();
+    let mut queued = HashSet::new();
+
+    // scan
+    for item in chart.keys() {
+        if !matches!(item.before().last(), Some(Token::Term(_))) {
+            continue;
+        }
+        let Some(&p) = sets[j - 1].get(&item.retreat(j - 1)) else {
+            continue;
+        };
+        out.insert(item.clone(), p);
+        if item.after().is_empty() && queued.insert(item.clone()) {
+            todo.entry(item.range().start)
+                .or_default()
+                .push(item.clone());
+        }
+    }
+
+    // complete, shortest items first
+    while let Some((k, children)) = todo.pop_last() {
+        for child in children {
+            let child_inner = out[&child].inner;
+            let y = closures.index[child.name()];
+            for (parent, p) in sets[k].iter() {
+                let Some(Token::NonTerm(z)) = parent.after().last() else {
+                    continue;
+                };
+                let Some(&z) = closures.index.get(z) else {
+                    continue;
+                };
+                let r = closures.unit[z][y];
+                if r == 0.0 {
+                    continue;
+                }
+                let item = parent.advance(j);
+                let q = out.entry(item.clone()).or_default();
+                q.forward += p.forward * r * child_inner;
+                q.inner += p.inner * r * child_inner;
+
+                let unit = item.before().len() == 1;
+                if item.after().is_empty() && !unit && queued.insert(item.clone()) {
+                    todo.entry(item.range().start).or_default().push(item);
+                }
+            }
+        }
+    }
+
+    // predict
+    let mut predicted = HashMap::new();
+    for (item, &p) in &out {
+        if let Some(Token::NonTerm(z)) = item.after().last() {
+            predict(grammar, closures, j, z, p, &mut predicted);
+        }
+    }
+    out.extend(predicted);
+    out
+}
+
+impl<T> PrefixParser<T>
+where
+    T: Clone + Eq + Hash + Display,
+{
+    /// Like [PrefixParser::new], but also keeps track of the probabilities of the tokens so far,
+    /// see [PrefixParser::prefix_probability].
+    ///
+    /// The weights of the productions are taken to be probabilities, see
+    /// [crate::Grammar::add_weighted_prod]. Fails if those of some nonterminal don't sum to 1,
+    /// if there are empty productions, or if the leftmost derivations of some nonterminal never
+    /// end.
+    pub fn with_probabilities(
+        grammar: impl Into<Arc<Grammar<T>>>,
+        initial: impl AsRef<str>,
+    ) -> Result<Self, ProbabilityError> {
+        let mut out = Self::new(grammar, initial.as_ref());
+        out.probabilities = Some(Tracking::new(&out.table.grammar, initial.as_ref())?);
+        Ok(out)
+    }
+
+    /// The probability that a string of the language starts with the tokens so far, `None` if
+    /// the parser doesn't keep track of it, see [PrefixParser::with_probabilities]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_weighted_prod("S", [Token::Term('a'), Token::NonTerm("S".into())], 0.25);
+    /// grammar.add_weighted_prod("S", [Token::Term('b')], 0.75);
+    ///
+    /// let mut parser = PrefixParser::with_probabilities(grammar, "S").unwrap();
+    /// parser.try_next('a').unwrap();
+    /// assert_eq!(parser.prefix_probability(), Some(0.25));
+    /// assert_eq!(parser.next_token_distribution().unwrap()[&'b'], 0.75);
+    /// ```
+    pub fn prefix_probability(&self) -> Option<f64> {
+        let sets = &self.probabilities.as_ref()?.sets;
+        let j = sets.len() - 1;
+        if j == 0 {
+            return Some(1.0);
+        }
+        let prefix = sets[j]
+            .iter()
+            .filter(|(item, _)| matches!(item.before().last(), Some(Token::Term(_))))
+            .map(|(_, p)| p.forward)
+            .sum();
+        Some(prefix)
+    }
+
+    /// The probability of every legal token being next, given the tokens so far. The
+    /// probability of the input ending here makes up the rest.
+    ///
+    /// `None` also if the tokens so far have probability 0. See
+    /// [PrefixParser::prefix_probability]
+    pub fn next_token_distribution(&self) -> Option<HashMap<T, f64>> {
+        let prefix = self.prefix_probability()?;
+        if prefix == 0.0 {
+            return None;
+        }
+        let mut out = HashMap::new();
+        for (item, p) in self.probabilities.as_ref()?.sets.last().unwrap().iter() {
+            if let Some(Token::Term(t)) = item.after().last() {
+                *out.entry(t.clone()).or_default() += p.forward / prefix;
+            }
+        }
+        Some(out)
+    }
+}
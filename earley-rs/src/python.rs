@@ -1,8 +1,11 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
 use crate::Grammar as Gram;
 use itertools::Itertools;
-use pyo3::{create_exception, prelude::*};
+use pyo3::{create_exception, exceptions::PyValueError, prelude::*};
 
 #[pyclass]
 #[derive(Clone)]
@@ -52,19 +55,29 @@ impl Token {
 #[pyclass]
 #[derive(Clone)]
 pub struct Grammar {
-    inner: Gram<String>,
+    /// shared with the parsers constructed from it, copied when modified after that
+    inner: Arc<Gram<String>>,
 }
 
 #[pymethods]
 impl Grammar {
     #[new]
     fn new() -> Self {
-        Grammar { inner: Gram::new() }
+        Grammar {
+            inner: Arc::new(Gram::new()),
+        }
     }
 
     fn add_prod(&mut self, nonterm: &str, expansion: Vec<Token>) {
-        self.inner
-            .add_prod(nonterm, expansion.into_iter().map(|x| x.inner));
+        Arc::make_mut(&mut self.inner).add_prod(nonterm, expansion.into_iter().map(|x| x.inner));
+    }
+
+    fn add_weighted_prod(&mut self, nonterm: &str, expansion: Vec<Token>, weight: f64) {
+        Arc::make_mut(&mut self.inner).add_weighted_prod(
+            nonterm,
+            expansion.into_iter().map(|x| x.inner),
+            weight,
+        );
     }
 
     fn __str__(&self) -> String {
@@ -98,7 +111,7 @@ impl Grammar {
 #[pyclass]
 pub struct PrefixParser {
     inner: crate::PrefixParser<String>,
-    progress: String,
+    progress: Vec<String>,
 }
 
 create_exception!(earley, ParseError, pyo3::exceptions::PyException);
@@ -116,25 +129,97 @@ impl PrefixParser {
         let inner = crate::PrefixParser::new(grammar.inner, initial);
         Self {
             inner,
-            progress: String::new(),
+            progress: Vec::new(),
         }
     }
 
+    /// like the constructor, but also keeps track of the probabilities of the tokens, see
+    /// [crate::PrefixParser::with_probabilities]
+    #[staticmethod]
+    pub fn with_probabilities(grammar: Grammar, initial: &str) -> PyResult<Self> {
+        let inner = crate::PrefixParser::with_probabilities(grammar.inner, initial)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+        Ok(Self {
+            inner,
+            progress: Vec::new(),
+        })
+    }
+
     pub fn try_next(&mut self, token: &str) -> PyResult<()> {
         self.inner.try_next(token.to_owned())?;
-        self.progress += token;
+        self.progress.push(token.to_owned());
+        Ok(())
+    }
+
+    pub fn try_extend(&mut self, tokens: Vec<String>) -> PyResult<()> {
+        self.inner.try_extend(tokens.iter().cloned())?;
+        self.progress.extend(tokens);
+        Ok(())
+    }
+
+    pub fn set_insertable(&mut self, terminals: Vec<(String, usize)>, max: usize) {
+        self.inner.set_insertable(terminals, max);
+    }
+
+    pub fn try_next_inserting(&mut self, token: &str) -> PyResult<Vec<String>> {
+        let inserted = self.inner.try_next_inserting(token.to_owned())?;
+        self.progress.extend(inserted.iter().cloned());
+        self.progress.push(token.to_owned());
+        Ok(inserted)
+    }
+
+    pub fn accepts(&self, tokens: Vec<String>) -> bool {
+        self.inner.accepts(&tokens)
+    }
+
+    pub fn shortest_completion(&self) -> Option<Vec<String>> {
+        self.inner.shortest_completion()
+    }
+
+    pub fn completions(&self, max_len: usize) -> Vec<Vec<String>> {
+        self.inner.completions(max_len)
+    }
+
+    pub fn fork(&self) -> Self {
+        Self {
+            inner: self.inner.fork(),
+            progress: self.progress.clone(),
+        }
+    }
+
+    pub fn undo(&mut self) -> PyResult<()> {
+        self.inner.undo()?;
+        self.progress.pop();
+        Ok(())
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            inner: self.inner.checkpoint(),
+        }
+    }
+
+    pub fn rollback(&mut self, checkpoint: &Checkpoint) -> PyResult<()> {
+        self.inner.rollback(&checkpoint.inner)?;
+        self.progress.truncate(checkpoint.inner.tokens());
         Ok(())
     }
 
     pub fn finish(&self) -> PyResult<()> {
-        self.inner.finish()?;
+        if !self.inner.is_complete() {
+            return Err(crate::Error.into());
+        }
         Ok(())
     }
 
+    pub fn is_complete(&self) -> bool {
+        self.inner.is_complete()
+    }
+
     pub fn __repr__(&self) -> String {
         format!(
             "PrefixParser(progress={:?}, <parser@0x{:x}>)",
-            self.progress,
+            self.progress(),
             (self as *const _) as usize
         )
     }
@@ -143,17 +228,63 @@ impl PrefixParser {
         self.inner.legal_tokens()
     }
 
+    pub fn prefix_probability(&self) -> Option<f64> {
+        self.inner.prefix_probability()
+    }
+
+    pub fn next_token_distribution(&self) -> Option<HashMap<String, f64>> {
+        self.inner.next_token_distribution()
+    }
+
     #[getter]
     pub fn progress(&self) -> String {
-        self.progress.clone()
+        self.progress.concat()
+    }
+}
+
+/// an opaque state of a [PrefixParser], see [crate::Checkpoint]
+#[pyclass]
+pub struct Checkpoint {
+    inner: crate::Checkpoint<String>,
+}
+
+#[pymethods]
+impl Checkpoint {
+    pub fn __repr__(&self) -> String {
+        format!("Checkpoint(tokens={})", self.inner.tokens())
+    }
+}
+
+#[pyclass]
+pub struct Vocabulary {
+    inner: crate::Vocabulary<String>,
+}
+
+#[pymethods]
+impl Vocabulary {
+    #[new]
+    pub fn new(entries: Vec<Vec<String>>) -> Self {
+        Self {
+            inner: crate::Vocabulary::new(entries),
+        }
+    }
+
+    pub fn mask(&self, parser: &PrefixParser) -> Vec<bool> {
+        self.inner.mask(&parser.inner)
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.inner.len()
     }
 }
 
 #[pymodule]
 pub fn earley(py: Python<'_>, m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PrefixParser>()?;
+    m.add_class::<Checkpoint>()?;
     m.add_class::<Grammar>()?;
     m.add_class::<Token>()?;
+    m.add_class::<Vocabulary>()?;
     m.add("ParseError", py.get_type_bound::<ParseError>())?;
     m.add_function(wrap_pyfunction!(term, m)?)?;
     m.add_function(wrap_pyfunction!(non_term, m)?)?;
@@ -0,0 +1,261 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use crate::{Error, Grammar, Token};
+
+/// A symbol of a compiled production, nonterminals are referred to by index
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Symbol<T> {
+    Term(T),
+    NonTerm(usize),
+}
+
+/// An item without back-pointers: the production, how far into it we are and where it started
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    production: usize,
+    dot: usize,
+    origin: usize,
+}
+
+/// Recognizer, can tell whether a string belongs to the language, but not how.
+///
+/// Compared to [crate::PrefixParser], the items are much smaller and don't record how they were
+/// inserted. Earley sets that can no longer be looked at are dropped, so e.g. for a long list
+/// written with left recursion, the memory used doesn't grow with the input.
+///
+/// # Examples
+///
+/// ```
+/// # use earley::*;
+/// let mut grammar = Grammar::new();
+/// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+/// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+/// let mut recognizer = Recognizer::new(&grammar, "S");
+/// assert!(recognizer.try_next('(').is_ok());
+/// assert!(recognizer.try_next(')').is_ok());
+/// assert!(recognizer.try_next(')').is_err());
+/// assert!(recognizer.is_complete());
+///
+/// assert!(Recognizer::new(&grammar, "S").recognize("((()))".chars()));
+/// ```
+pub struct Recognizer<T> {
+    /// (lhs, rhs)
+    productions: Vec<(usize, Vec<Symbol<T>>)>,
+    /// the productions of every nonterminal
+    by_lhs: Vec<Vec<usize>>,
+    nullable: Vec<bool>,
+    initial: usize,
+    /// the sets that are still kept, by position
+    sets: BTreeMap<usize, Vec<Item>>,
+    /// the position of the last set
+    position: usize,
+}
+
+impl<T> Recognizer<T>
+where
+    T: Clone + Eq + std::hash::Hash,
+{
+    pub fn new(grammar: &Grammar<T>, initial: impl AsRef<str>) -> Self {
+        let mut names: HashMap<&str, usize> = HashMap::new();
+        for name in grammar.productions.keys() {
+            let len = names.len();
+            names.insert(name, len);
+        }
+        let mut productions = Vec::new();
+        let mut by_lhs = vec![Vec::new(); names.len()];
+        for (name, expansions) in &grammar.productions {
+            for expansion in expansions {
+                let rhs: Vec<_> = expansion
+                    .iter()
+                    .map(|token| match token {
+                        Token::Term(t) => Symbol::Term(t.clone()),
+                        Token::NonTerm(nt) => Symbol::NonTerm(
+                            *names
+                                .get(nt.as_str())
+                                .expect("grammar must contain nonterminal"),
+                        ),
+                    })
+                    .collect();
+                by_lhs[names[name.as_str()]].push(productions.len());
+                productions.push((names[name.as_str()], rhs));
+            }
+        }
+
+        let mut nullable = vec![false; names.len()];
+        loop {
+            let mut changed = false;
+            for (lhs, rhs) in &productions {
+                if nullable[*lhs] {
+                    continue;
+                }
+                if rhs
+                    .iter()
+                    .all(|symbol| matches!(symbol, &Symbol::NonTerm(nt) if nullable[nt]))
+                {
+                    nullable[*lhs] = true;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+
+        let initial = *names
+            .get(initial.as_ref())
+            .expect("grammar must contain initial");
+        let mut out = Self {
+            productions,
+            by_lhs,
+            nullable,
+            initial,
+            sets: BTreeMap::new(),
+            position: 0,
+        };
+        let seeds = out.by_lhs[initial]
+            .iter()
+            .map(|&production| Item {
+                production,
+                dot: 0,
+                origin: 0,
+            })
+            .collect();
+        let set = out.closure(0, seeds);
+        out.sets.insert(0, set);
+        out
+    }
+
+    fn next_symbol(&self, item: &Item) -> Option<&Symbol<T>> {
+        self.productions[item.production].1.get(item.dot)
+    }
+
+    fn advance(item: &Item) -> Item {
+        Item {
+            dot: item.dot + 1,
+            ..*item
+        }
+    }
+
+    /// predicts and completes the set at position `j`, using the Aycock-Horspool trick for
+    /// nullable nonterminals
+    fn closure(&self, j: usize, seeds: Vec<Item>) -> Vec<Item> {
+        let mut set = seeds;
+        let mut seen: HashSet<Item> = set.iter().copied().collect();
+        let mut i = 0;
+        while i < set.len() {
+            let item = set[i];
+            i += 1;
+            let mut add = |new: Item| {
+                if seen.insert(new) {
+                    set.push(new);
+                }
+            };
+            match self.next_symbol(&item) {
+                Some(Symbol::Term(_)) => {}
+                Some(&Symbol::NonTerm(nt)) => {
+                    for &production in &self.by_lhs[nt] {
+                        add(Item {
+                            production,
+                            dot: 0,
+                            origin: j,
+                        });
+                    }
+                    if self.nullable[nt] {
+                        add(Self::advance(&item));
+                    }
+                }
+                None => {
+                    let lhs = self.productions[item.production].0;
+                    // items from set j are handled by the nullable check above
+                    if item.origin == j {
+                        continue;
+                    }
+                    let origin = self
+                        .sets
+                        .get(&item.origin)
+                        .expect("sets that items start in are kept");
+                    for parent in origin {
+                        if self.next_symbol(parent) == Some(&Symbol::NonTerm(lhs)) {
+                            add(Self::advance(parent));
+                        }
+                    }
+                }
+            }
+        }
+        set
+    }
+
+    /// Attempts to advance the state
+    pub fn try_next(&mut self, token: T) -> Result<(), Error> {
+        let token = Symbol::Term(token);
+        let seeds: Vec<Item> = self
+            .last()
+            .iter()
+            .filter(|item| self.next_symbol(item) == Some(&token))
+            .map(Self::advance)
+            .collect();
+        if seeds.is_empty() {
+            return Err(Error);
+        }
+        let set = self.closure(self.position + 1, seeds);
+        self.position += 1;
+        self.sets.insert(self.position, set);
+        self.drop_dead_sets();
+        Ok(())
+    }
+
+    fn last(&self) -> &Vec<Item> {
+        &self.sets[&self.position]
+    }
+
+    /// drops all sets that no item will ever look at again
+    ///
+    /// Completing an item looks into the set it started in, where the items found may later be
+    /// completed as well. So only the sets reachable from the last one through the origins of
+    /// their items are live, and finding them only looks at those
+    fn drop_dead_sets(&mut self) {
+        let mut live = BTreeSet::new();
+        let mut todo = vec![self.position];
+        while let Some(j) = todo.pop() {
+            if !live.insert(j) {
+                continue;
+            }
+            let set = &self.sets[&j];
+            todo.extend(
+                set.iter()
+                    .map(|item| item.origin)
+                    .filter(|k| !live.contains(k)),
+            );
+        }
+        self.sets.retain(|j, _| live.contains(j));
+    }
+
+    /// Whether the tokens so far form a string of the initial nonterminal
+    pub fn is_complete(&self) -> bool {
+        self.last().iter().any(|item| {
+            item.origin == 0
+                && self.productions[item.production].0 == self.initial
+                && self.next_symbol(item).is_none()
+        })
+    }
+
+    /// Feeds all of `input` into the recognizer, and checks whether the result is complete
+    pub fn recognize(mut self, input: impl IntoIterator<Item = T>) -> bool {
+        input.into_iter().all(|token| self.try_next(token).is_ok()) && self.is_complete()
+    }
+
+    pub fn legal_tokens(&self) -> HashSet<T> {
+        self.last()
+            .iter()
+            .filter_map(|item| match self.next_symbol(item) {
+                Some(Symbol::Term(t)) => Some(t.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the number of Earley sets that are still kept around
+    pub fn live_sets(&self) -> usize {
+        self.sets.len()
+    }
+}
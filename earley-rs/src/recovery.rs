@@ -0,0 +1,506 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    fmt::Display,
+    hash::Hash,
+};
+
+use crate::{Error, Grammar, ParseInfo, Parser, SyntaxError, Token};
+
+/// How much each kind of repair costs, see [Parser::parse_with_recovery].
+///
+/// Every repair costs 1 unless configured otherwise, a cost of `usize::MAX` forbids it.
+#[derive(Debug, Clone)]
+pub struct RepairCosts<T> {
+    insert: HashMap<T, usize>,
+    delete: HashMap<T, usize>,
+    substitute: HashMap<T, usize>,
+}
+
+impl<T: Eq + Hash> RepairCosts<T> {
+    pub fn new() -> Self {
+        Self {
+            insert: HashMap::new(),
+            delete: HashMap::new(),
+            substitute: HashMap::new(),
+        }
+    }
+
+    /// the cost of inserting `token`
+    pub fn insert(mut self, token: T, cost: usize) -> Self {
+        self.insert.insert(token, cost);
+        self
+    }
+
+    /// the cost of deleting `token`
+    pub fn delete(mut self, token: T, cost: usize) -> Self {
+        self.delete.insert(token, cost);
+        self
+    }
+
+    /// the cost of replacing any token with `token`
+    pub fn substitute(mut self, token: T, cost: usize) -> Self {
+        self.substitute.insert(token, cost);
+        self
+    }
+
+    fn insert_cost(&self, token: &T) -> usize {
+        self.insert.get(token).copied().unwrap_or(1)
+    }
+
+    fn delete_cost(&self, token: &T) -> usize {
+        self.delete.get(token).copied().unwrap_or(1)
+    }
+
+    fn substitute_cost(&self, token: &T) -> usize {
+        self.substitute.get(token).copied().unwrap_or(1)
+    }
+}
+
+impl<T: Eq + Hash> Default for RepairCosts<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A change to the input, positions are indices into the original input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Repair<T> {
+    /// `token` was inserted before the token at `position`
+    Insert { position: usize, token: T },
+    /// the token at `position` was dropped
+    Delete { position: usize, token: T },
+    /// the token at `position` was replaced with `expected`
+    Substitute {
+        position: usize,
+        found: T,
+        expected: T,
+    },
+}
+
+/// The result of [Parser::parse_with_recovery]
+pub struct Recovered<T> {
+    /// the parse of the repaired input
+    pub parse: ParseInfo<T>,
+    /// the input after all repairs
+    pub tokens: Vec<T>,
+    /// the repairs, ordered by position
+    pub repairs: Vec<Repair<T>>,
+    /// the sum of the costs of the repairs
+    pub cost: usize,
+}
+
+/// Synchronization terminals and recovery nonterminals, see [Parser::parse_panic_mode]
+#[derive(Debug, Clone)]
+pub struct PanicMode<T> {
+    sync: HashSet<T>,
+    recovery: HashSet<String>,
+}
+
+impl<T: Eq + Hash> PanicMode<T> {
+    pub fn new(
+        sync: impl IntoIterator<Item = T>,
+        recovery: impl IntoIterator<Item = impl AsRef<str>>,
+    ) -> Self {
+        Self {
+            sync: sync.into_iter().collect(),
+            recovery: recovery
+                .into_iter()
+                .map(|x| x.as_ref().to_owned())
+                .collect(),
+        }
+    }
+}
+
+/// The result of [Parser::parse_panic_mode]
+pub struct Synchronized<T> {
+    /// the parse of the input, with an error node wherever the input was skipped
+    pub parse: ParseInfo<T>,
+    /// the errors that were recovered from, in order
+    pub errors: Vec<SyntaxError<T>>,
+}
+
+/// (nonterminal, production, dot, origin)
+type Key<'g> = (&'g str, usize, usize, usize);
+
+/// how an item got its cost
+#[derive(Debug, Clone, Copy)]
+enum Back<'g> {
+    Pred,
+    /// the item in the previous set, expecting the token
+    Scan(Key<'g>),
+    /// the item in the previous set, expecting another terminal than the token
+    Substitute(Key<'g>),
+    /// the same item in the previous set
+    Delete(Key<'g>),
+    /// the item in the same set, expecting the inserted terminal
+    Insert(Key<'g>),
+    /// the parent in the set the child starts in, and the completed child in this set
+    Comp(Key<'g>, Key<'g>),
+}
+
+/// An Earley chart where every item carries the cheapest cost of repairing the input it spans
+/// into something its symbols so far derive
+struct Chart<'g, 'c, T> {
+    grammar: &'g Grammar<T>,
+    costs: &'c RepairCosts<T>,
+    input: Vec<T>,
+    sets: Vec<HashMap<Key<'g>, (usize, Back<'g>)>>,
+}
+
+impl<'g, T> Chart<'g, '_, T>
+where
+    T: Clone + Eq + Hash + Display,
+{
+    fn next_symbol(&self, (name, production, dot, _): Key<'g>) -> Option<&'g Token<T>> {
+        self.grammar.productions[name][production].get(dot)
+    }
+
+    fn advance((name, production, dot, origin): Key<'g>) -> Key<'g> {
+        (name, production, dot + 1, origin)
+    }
+
+    fn relax(
+        tentative: &mut HashMap<Key<'g>, (usize, Back<'g>)>,
+        heap: &mut BinaryHeap<Reverse<(usize, Key<'g>)>>,
+        key: Key<'g>,
+        cost: usize,
+        back: Back<'g>,
+    ) {
+        if cost == usize::MAX || tentative.get(&key).is_some_and(|&(c, _)| c <= cost) {
+            return;
+        }
+        tentative.insert(key, (cost, back));
+        heap.push(Reverse((cost, key)));
+    }
+
+    /// Computes set `j` from the items that scanned or deleted into it, by running dijkstra on
+    /// the predictions, completions and insertions within the set
+    fn close(&mut self, mut tentative: HashMap<Key<'g>, (usize, Back<'g>)>) {
+        let j = self.sets.len();
+        let mut heap: BinaryHeap<_> = tentative
+            .iter()
+            .map(|(&key, &(cost, _))| Reverse((cost, key)))
+            .collect();
+        let mut done: HashMap<Key<'g>, (usize, Back<'g>)> = HashMap::new();
+        // the finished items of this set expecting a nonterminal, and the finished complete
+        // items starting here, by nonterminal
+        let mut waiting: HashMap<&str, Vec<(Key<'g>, usize)>> = HashMap::new();
+        let mut complete: HashMap<&str, Vec<(Key<'g>, usize)>> = HashMap::new();
+
+        while let Some(Reverse((cost, key))) = heap.pop() {
+            if done.contains_key(&key) || tentative[&key].0 < cost {
+                continue;
+            }
+            done.insert(key, tentative[&key]);
+            match self.next_symbol(key) {
+                Some(Token::Term(t)) => {
+                    let cost = cost.saturating_add(self.costs.insert_cost(t));
+                    let next = Self::advance(key);
+                    Self::relax(&mut tentative, &mut heap, next, cost, Back::Insert(key));
+                }
+                Some(Token::NonTerm(nt)) => {
+                    let (name, productions) = self
+                        .grammar
+                        .productions
+                        .get_key_value(nt)
+                        .expect("grammar must contain nonterminal");
+                    for production in 0..productions.len() {
+                        let child = (name.as_str(), production, 0, j);
+                        Self::relax(&mut tentative, &mut heap, child, 0, Back::Pred);
+                    }
+                    for &(child, child_cost) in complete.get(nt.as_str()).into_iter().flatten() {
+                        let next = Self::advance(key);
+                        let back = Back::Comp(key, child);
+                        let cost = cost.saturating_add(child_cost);
+                        Self::relax(&mut tentative, &mut heap, next, cost, back);
+                    }
+                    waiting.entry(nt).or_default().push((key, cost));
+                }
+                None => {
+                    let (name, .., origin) = key;
+                    let parents: Vec<(Key<'g>, usize)> = if origin == j {
+                        complete.entry(name).or_default().push((key, cost));
+                        waiting.get(name).cloned().unwrap_or_default()
+                    } else {
+                        self.sets[origin]
+                            .iter()
+                            .filter(|&(&parent, _)| {
+                                matches!(self.next_symbol(parent), Some(Token::NonTerm(nt)) if nt == name)
+                            })
+                            .map(|(&parent, &(cost, _))| (parent, cost))
+                            .collect()
+                    };
+                    for (parent, parent_cost) in parents {
+                        let next = Self::advance(parent);
+                        let back = Back::Comp(parent, key);
+                        let cost = parent_cost.saturating_add(cost);
+                        Self::relax(&mut tentative, &mut heap, next, cost, back);
+                    }
+                }
+            }
+        }
+        self.sets.push(done);
+    }
+
+    /// the items set `j + 1` starts with, by scanning, substituting or deleting the token `j`
+    fn shift(&self, j: usize) -> HashMap<Key<'g>, (usize, Back<'g>)> {
+        let token = &self.input[j];
+        let mut out: HashMap<Key<'g>, (usize, Back<'g>)> = HashMap::new();
+        let mut add = |key: Key<'g>, cost: usize, back: Back<'g>| {
+            if cost != usize::MAX && out.get(&key).is_none_or(|&(c, _)| cost < c) {
+                out.insert(key, (cost, back));
+            }
+        };
+        for (&key, &(cost, _)) in &self.sets[j] {
+            add(
+                key,
+                cost.saturating_add(self.costs.delete_cost(token)),
+                Back::Delete(key),
+            );
+            match self.next_symbol(key) {
+                Some(Token::Term(t)) if t == token => {
+                    add(Self::advance(key), cost, Back::Scan(key))
+                }
+                Some(Token::Term(t)) => add(
+                    Self::advance(key),
+                    cost.saturating_add(self.costs.substitute_cost(t)),
+                    Back::Substitute(key),
+                ),
+                _ => {}
+            }
+        }
+        out
+    }
+
+    /// appends the tokens that item `key` in set `j` spans after the repairs
+    fn walk(&self, j: usize, key: Key<'g>, tokens: &mut Vec<T>, repairs: &mut Vec<Repair<T>>) {
+        let previous = |key: Key<'g>| match self.grammar.productions[key.0][key.1][key.2 - 1] {
+            Token::Term(ref t) => t.clone(),
+            Token::NonTerm(_) => unreachable!("only terminals are scanned"),
+        };
+        match self.sets[j][&key].1 {
+            Back::Pred => {}
+            Back::Scan(prev) => {
+                self.walk(j - 1, prev, tokens, repairs);
+                tokens.push(self.input[j - 1].clone());
+            }
+            Back::Substitute(prev) => {
+                self.walk(j - 1, prev, tokens, repairs);
+                tokens.push(previous(key));
+                repairs.push(Repair::Substitute {
+                    position: j - 1,
+                    found: self.input[j - 1].clone(),
+                    expected: previous(key),
+                });
+            }
+            Back::Delete(prev) => {
+                self.walk(j - 1, prev, tokens, repairs);
+                repairs.push(Repair::Delete {
+                    position: j - 1,
+                    token: self.input[j - 1].clone(),
+                });
+            }
+            Back::Insert(prev) => {
+                self.walk(j, prev, tokens, repairs);
+                tokens.push(previous(key));
+                repairs.push(Repair::Insert {
+                    position: j,
+                    token: previous(key),
+                });
+            }
+            Back::Comp(parent, child) => {
+                self.walk(child.3, parent, tokens, repairs);
+                self.walk(j, child, tokens, repairs);
+            }
+        }
+    }
+}
+
+impl<T, I> Parser<T, I>
+where
+    I: Iterator<Item = T>,
+    T: Clone + Eq + Hash + Display,
+{
+    /// Parses the input after the cheapest sequence of insertions, deletions and substitutions
+    /// of tokens that makes it complete.
+    ///
+    /// Fails only if the initial nonterminals don't derive any string.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// let costs = RepairCosts::new().substitute('(', 3).substitute(')', 3);
+    /// let recovered = Parser::new("(x)".chars(), grammar, "S")
+    ///     .parse_with_recovery(&costs)
+    ///     .unwrap();
+    /// assert_eq!(recovered.tokens, "()".chars().collect::<Vec<_>>());
+    /// assert_eq!(recovered.repairs, [Repair::Delete { position: 1, token: 'x' }]);
+    /// ```
+    pub fn parse_with_recovery(mut self, costs: &RepairCosts<T>) -> Result<Recovered<T>, Error> {
+        let grammar = self.table.grammar.clone();
+        let mut chart = Chart {
+            grammar: &grammar,
+            costs,
+            input: self.input.by_ref().collect(),
+            sets: Vec::new(),
+        };
+
+        let mut seeds = HashMap::new();
+        for initial in &self.table.initials {
+            let (name, productions) = grammar
+                .productions
+                .get_key_value(initial)
+                .expect("grammar must contain initial");
+            for production in 0..productions.len() {
+                seeds.insert((name.as_str(), production, 0, 0), (0, Back::Pred));
+            }
+        }
+        chart.close(seeds);
+        for j in 0..chart.input.len() {
+            let next = chart.shift(j);
+            chart.close(next);
+        }
+
+        let n = chart.input.len();
+        // the cheapest complete item, preferring earlier initials on ties
+        let initials = &self.table.initials;
+        let (root, cost) = chart.sets[n]
+            .iter()
+            .filter(|&(&key, _)| key.3 == 0 && chart.next_symbol(key).is_none())
+            .filter_map(|(&key, &(cost, _))| {
+                let rank = initials.iter().position(|x| x == key.0)?;
+                Some((key, cost, rank))
+            })
+            .min_by_key(|&(_, cost, rank)| (cost, rank))
+            .map(|(key, cost, _)| (key, cost))
+            .ok_or(Error)?;
+
+        let mut tokens = Vec::new();
+        let mut repairs = Vec::new();
+        chart.walk(n, root, &mut tokens, &mut repairs);
+        for token in &tokens {
+            self.table.next(token.clone());
+        }
+        Ok(Recovered {
+            parse: self.table.into_parse().ok_or(Error)?,
+            tokens,
+            repairs,
+            cost,
+        })
+    }
+
+    /// Parses the input, recovering from errors by skipping to the next synchronization
+    /// terminal.
+    ///
+    /// When a token can't be accepted, the parse goes back to the latest position that expects a
+    /// recovery nonterminal, lets that nonterminal derive an error spanning up to the next
+    /// synchronization terminal, and resumes from there. If the synchronization terminal can't
+    /// be accepted after that, the one after it is tried, and so on. An error never starts
+    /// before the previous one ends, and is only empty if that doesn't let some nonterminal
+    /// derive itself, e.g. through `S -> S S`. Fails with all errors if none of them works.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::{latex::ParseTree, *};
+    /// # fn t(c: char) -> Token<char> {
+    /// #     Token::Term(c)
+    /// # }
+    /// # fn nt(c: impl ToString) -> Token<char> {
+    /// #     Token::NonTerm(c.to_string())
+    /// # }
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("stats", [nt("stats"), nt("stat"), t(';')]);
+    /// grammar.add_prod("stats", [nt("stat"), t(';')]);
+    /// grammar.add_prod("stat", [t('x'), t('='), t('1')]);
+    ///
+    /// let mode = PanicMode::new([';'], ["stat"]);
+    /// let synchronized = Parser::new("x=1;x==1;x=1;".chars(), grammar, "stats")
+    ///     .parse_panic_mode(&mode)
+    ///     .unwrap();
+    /// assert_eq!(synchronized.errors[0].position, 6);
+    ///
+    /// let tree = synchronized.parse.tree();
+    /// let ParseTree::NonTerminal(_, children) = &tree else { unreachable!() };
+    /// let ParseTree::NonTerminal(_, children) = &children[0] else { unreachable!() };
+    /// assert!(matches!(&children[1], ParseTree::Error("stat", range) if range == &(4..8)));
+    /// ```
+    pub fn parse_panic_mode(
+        mut self,
+        mode: &PanicMode<T>,
+    ) -> Result<Synchronized<T>, Vec<SyntaxError<T>>> {
+        let input: Vec<T> = self.input.by_ref().collect();
+        let mut errors = Vec::new();
+        let mut position = 0;
+        // where the last error ended, the errors before it are final
+        let mut resumed = 0;
+        loop {
+            if let Some(token) = input.get(position) {
+                self.table.next(token.clone());
+                if !self.table.table.last().unwrap().is_empty() {
+                    position += 1;
+                    continue;
+                }
+                self.table.table.pop();
+            } else if self.table.is_complete() {
+                break;
+            }
+
+            errors.push(
+                self.table
+                    .syntax_error(position, input.get(position).cloned()),
+            );
+            match self.synchronize(resumed, position, &input, mode) {
+                Some(end) => (position, resumed) = (end, end),
+                None => return Err(errors),
+            }
+        }
+        Ok(Synchronized {
+            parse: self.table.into_parse().expect("table is complete"),
+            errors,
+        })
+    }
+
+    /// Finds the error after which the token at `end` can be accepted, or the input is complete
+    /// if `end` is past it, for the earliest possible `end`. The error starts no earlier than
+    /// `from`, so that the errors before it stay in the table.
+    ///
+    /// The table then ends with set `end`, which is returned
+    fn synchronize(
+        &mut self,
+        from: usize,
+        position: usize,
+        input: &[T],
+        mode: &PanicMode<T>,
+    ) -> Option<usize> {
+        let ends = (position..input.len())
+            .filter(|&end| mode.sync.contains(&input[end]))
+            .chain([input.len()]);
+        for end in ends {
+            for start in (from..=position).rev() {
+                let expects_recovery = self.table.table[start].keys().any(|item| {
+                    matches!(item.after().last(), Some(Token::NonTerm(nt)) if mode.recovery.contains(nt))
+                });
+                if !expects_recovery {
+                    continue;
+                }
+                let mut table = self.table.clone();
+                table.push_error(start, end, &mode.recovery);
+                let resumes = match input.get(end) {
+                    Some(token) => table.legal_tokens().contains(token),
+                    None => table.is_complete(),
+                };
+                if resumes {
+                    self.table = table;
+                    return Some(end);
+                }
+            }
+        }
+        None
+    }
+}
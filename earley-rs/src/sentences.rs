@@ -0,0 +1,283 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{generate::Rng, Grammar, Token};
+
+/// the sentences of one length every nonterminal derives
+type Level<'a, T> = HashMap<&'a str, BTreeSet<Vec<T>>>;
+
+/// a number of derivations, `None` if there are infinitely many or too many to count
+type Count = Option<u128>;
+
+fn add(a: Count, b: Count) -> Count {
+    a?.checked_add(b?)
+}
+
+fn mul(a: Count, b: Count) -> Count {
+    if a == Some(0) || b == Some(0) {
+        return Some(0);
+    }
+    a?.checked_mul(b?)
+}
+
+impl<T: Clone + Ord> Grammar<T> {
+    /// All sentences `start` derives that are at most `max_len` tokens long, shortest first and
+    /// sorted among those of the same length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::NonTerm("S".into()), Token::NonTerm("S".into())]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// let sentences: Vec<String> = grammar
+    ///     .sentences("S", 4)
+    ///     .map(|x| x.into_iter().collect())
+    ///     .collect();
+    /// assert_eq!(sentences, ["()", "(())", "()()"]);
+    /// ```
+    pub fn sentences(&self, start: &str, max_len: usize) -> impl Iterator<Item = Vec<T>> + '_ {
+        let start = start.to_owned();
+        let mut levels: Vec<Level<T>> = Vec::new();
+        (0..=max_len).flat_map(move |_| {
+            let level = self.next_level(&levels);
+            let out: Vec<Vec<T>> = level
+                .get(start.as_str())
+                .into_iter()
+                .flatten()
+                .cloned()
+                .collect();
+            levels.push(level);
+            out
+        })
+    }
+
+    /// The sentences of the next length after `levels`.
+    ///
+    /// With empty productions, a nonterminal may derive sentences of the same length as the
+    /// nonterminals in its productions, so this is repeated until nothing changes
+    fn next_level<'a>(&'a self, levels: &[Level<'a, T>]) -> Level<'a, T> {
+        let n = levels.len();
+        let mut current: Level<T> = HashMap::new();
+        loop {
+            let mut next: Level<T> = HashMap::new();
+            for (name, productions) in &self.productions {
+                for production in productions {
+                    let mut out = Vec::new();
+                    concatenations(production, n, levels, &current, &mut Vec::new(), &mut out);
+                    if !out.is_empty() {
+                        next.entry(name.as_str()).or_default().extend(out);
+                    }
+                }
+            }
+            if next == current {
+                return current;
+            }
+            current = next;
+        }
+    }
+}
+
+/// every sentence `symbols` derive that is `n` tokens long, after `prefix`
+fn concatenations<T: Clone + Ord>(
+    symbols: &[Token<T>],
+    n: usize,
+    levels: &[Level<T>],
+    current: &Level<T>,
+    prefix: &mut Vec<T>,
+    out: &mut Vec<Vec<T>>,
+) {
+    let Some((first, rest)) = symbols.split_first() else {
+        if n == 0 {
+            out.push(prefix.clone());
+        }
+        return;
+    };
+    match first {
+        Token::Term(_) if n == 0 => {}
+        Token::Term(t) => {
+            prefix.push(t.clone());
+            concatenations(rest, n - 1, levels, current, prefix, out);
+            prefix.pop();
+        }
+        Token::NonTerm(nt) => {
+            for len in 0..=n {
+                let level = levels.get(len).unwrap_or(current);
+                for sentence in level.get(nt.as_str()).into_iter().flatten() {
+                    let before = prefix.len();
+                    prefix.extend(sentence.iter().cloned());
+                    concatenations(rest, n - len, levels, current, prefix, out);
+                    prefix.truncate(before);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Clone> Grammar<T> {
+    /// The number of derivations of sentences of `n` tokens from `start`, which is the number of
+    /// such sentences if the grammar is unambiguous.
+    ///
+    /// Returns `None` if there are infinitely many, because of cycles of productions that derive
+    /// the same sentence, or too many to count.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use earley::*;
+    /// let mut grammar = Grammar::new();
+    /// grammar.add_prod("S", [Token::NonTerm("S".into()), Token::NonTerm("S".into())]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+    /// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+    /// // ()()() is derived in two ways
+    /// assert_eq!(grammar.count_sentences("S", 6), Some(6));
+    /// assert_eq!(grammar.sentences("S", 6).filter(|x| x.len() == 6).count(), 5);
+    /// ```
+    pub fn count_sentences(&self, start: &str, n: usize) -> Option<u128> {
+        let counts = self.derivation_counts(n);
+        counts[n].get(start).copied().unwrap_or(Some(0))
+    }
+
+    /// A random sentence of `n` tokens derived from `start`, with every derivation equally
+    /// likely, so every sentence if the grammar is unambiguous.
+    ///
+    /// Returns `None` if there is no such sentence, or if [Grammar::count_sentences] can't count
+    /// them.
+    pub fn sample_sentence(&self, start: &str, n: usize, rng: &mut impl Rng) -> Option<Vec<T>> {
+        let counts = self.derivation_counts(n);
+        match counts[n].get(start).copied()? {
+            None | Some(0) => return None,
+            Some(_) => {}
+        }
+        let mut out = Vec::new();
+        self.sample(&counts, start, n, rng, &mut out);
+        Some(out)
+    }
+
+    /// `out[m][A]` is the number of derivations of sentences of `m` tokens from `A`, for every
+    /// `m` up to `n`.
+    ///
+    /// Like [Grammar::sentences], every length is repeated until nothing changes. The counts
+    /// that still change once every chain of nonterminals had the chance to settle are on or
+    /// below a cycle, and set to infinity.
+    fn derivation_counts(&self, n: usize) -> Vec<HashMap<&str, Count>> {
+        let mut levels: Vec<HashMap<&str, Count>> = Vec::new();
+        for m in 0..=n {
+            let mut current: HashMap<&str, Count> = self
+                .productions
+                .keys()
+                .map(|name| (name.as_str(), Some(0)))
+                .collect();
+            for pass in 0.. {
+                let count = |nt: &str, len: usize| {
+                    let level = levels.get(len).unwrap_or(&current);
+                    level.get(nt).copied().unwrap_or(Some(0))
+                };
+                let mut next: HashMap<&str, Count> = self
+                    .productions
+                    .iter()
+                    .map(|(name, productions)| {
+                        let total = productions
+                            .iter()
+                            .map(|production| ways(production, m, count)[m])
+                            .fold(Some(0), add);
+                        (name.as_str(), total)
+                    })
+                    .collect();
+                if next == current {
+                    break;
+                }
+                if pass > 2 * self.productions.len() + 2 {
+                    for (name, value) in next.iter_mut() {
+                        if current[name] != *value {
+                            *value = None;
+                        }
+                    }
+                }
+                current = next;
+            }
+            levels.push(current);
+        }
+        levels
+    }
+
+    /// appends a random sentence of `n` tokens derived from `name` to `out`
+    fn sample(
+        &self,
+        counts: &[HashMap<&str, Count>],
+        name: &str,
+        n: usize,
+        rng: &mut impl Rng,
+        out: &mut Vec<T>,
+    ) {
+        let count = |nt: &str, len: usize| counts[len].get(nt).copied().unwrap_or(Some(0));
+        let productions = &self.productions[name];
+        let weights: Vec<u128> = productions
+            .iter()
+            .map(|production| finite(ways(production, n, count)[n]))
+            .collect();
+        let production = &productions[choose(rng, &weights)];
+
+        let mut left = n;
+        for (i, symbol) in production.iter().enumerate() {
+            match symbol {
+                Token::Term(t) => {
+                    out.push(t.clone());
+                    left -= 1;
+                }
+                Token::NonTerm(nt) => {
+                    let rest = ways(&production[i + 1..], left, count);
+                    let weights: Vec<u128> = (0..=left)
+                        .map(|len| finite(mul(count(nt, len), rest[left - len])))
+                        .collect();
+                    let len = choose(rng, &weights);
+                    self.sample(counts, nt, len, rng, out);
+                    left -= len;
+                }
+            }
+        }
+    }
+}
+
+/// `out[m]` is the number of derivations of sentences of `m` tokens from `symbols`, for every
+/// `m` up to `n`, given those of the nonterminals
+fn ways<T>(symbols: &[Token<T>], n: usize, count: impl Fn(&str, usize) -> Count) -> Vec<Count> {
+    let mut out = vec![Some(0); n + 1];
+    out[0] = Some(1);
+    for symbol in symbols {
+        out = (0..=n)
+            .map(|m| match symbol {
+                Token::Term(_) if m == 0 => Some(0),
+                Token::Term(_) => out[m - 1],
+                Token::NonTerm(nt) => (0..=m)
+                    .map(|len| mul(out[m - len], count(nt, len)))
+                    .fold(Some(0), add),
+            })
+            .collect();
+    }
+    out
+}
+
+/// the counts met while sampling are finite, since the total is
+fn finite(count: Count) -> u128 {
+    count.expect("the total number of derivations is finite")
+}
+
+/// a random index, with probabilities proportional to `weights`
+fn choose(rng: &mut impl Rng, weights: &[u128]) -> usize {
+    let total = weights.iter().sum::<u128>() as f64;
+    let mut choice = rng.next_f64() * total;
+    let mut last = 0;
+    for (i, &weight) in weights.iter().enumerate() {
+        if weight == 0 {
+            continue;
+        }
+        choice -= weight as f64;
+        if choice < 0.0 {
+            return i;
+        }
+        last = i;
+    }
+    last
+}
@@ -1,11 +1,12 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt::{Debug, Display},
+    sync::Arc,
 };
 
 use itertools::Itertools;
 
-use crate::{Grammar, InsertedBy, Range, Token};
+use crate::{Grammar, InsertedBy, ParseInfo, Range, SyntaxError, Token};
 
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub(crate) struct Item<T> {
@@ -90,10 +91,50 @@ impl<T> Item<T> {
     }
 }
 
+impl<T: Clone> Item<T> {
+    /// the same item before the dot moved over its last symbol, which ended at `end`
+    pub(crate) fn retreat(&self, end: usize) -> Self {
+        let mut before = self.before.clone();
+        let mut after = self.after.clone();
+        after.push(before.pop().expect("the dot has moved over a symbol"));
+        Self {
+            range: self.range.start..end,
+            name: self.name.clone(),
+            before,
+            after,
+        }
+    }
+
+    /// the same item with the dot moved over its next symbol, which ends at `end`
+    pub(crate) fn advance(&self, end: usize) -> Self {
+        let mut before = self.before.clone();
+        let mut after = self.after.clone();
+        before.push(after.pop().expect("the dot is before a symbol"));
+        Self {
+            range: self.range.start..end,
+            name: self.name.clone(),
+            before,
+            after,
+        }
+    }
+
+    /// the same item with its range moved by `f`
+    pub(crate) fn map_range(&self, f: impl Fn(usize) -> usize) -> Self {
+        Self {
+            range: f(self.range.start)..f(self.range.end),
+            ..self.clone()
+        }
+    }
+}
+
+/// The Earley sets are never modified once pushed, so they are shared between clones
+#[derive(Clone)]
 pub struct Table<T> {
-    pub(super) table: Vec<HashMap<Item<T>, InsertedBy>>,
-    grammar: Grammar<T>,
-    pub(super) initial: String,
+    pub(super) table: Vec<Arc<HashMap<Item<T>, InsertedBy>>>,
+    pub(super) grammar: Arc<Grammar<T>>,
+    /// the nonterminals a complete parse may be a string of, in order of preference
+    pub(super) initials: Vec<String>,
+    seed_everywhere: bool,
 }
 
 impl<T: Debug> Debug for Table<T> {
@@ -112,28 +153,48 @@ impl<T> Table<T>
 where
     T: Clone + std::cmp::Eq + std::hash::Hash + Display,
 {
-    pub fn new(grammar: Grammar<T>, initial: impl AsRef<str>, size_hint: usize) -> Self {
-        let initials = grammar
-            .productions
-            .get(initial.as_ref())
-            .expect("grammar must contain initial")
-            .iter()
-            .cloned()
-            .map(|x| {
-                (
-                    Item::init(initial.as_ref().to_owned(), x, 0..0),
-                    InsertedBy::Pred,
-                )
-            })
-            .collect();
+    pub fn new(
+        grammar: impl Into<Arc<Grammar<T>>>,
+        initials: impl IntoIterator<Item = impl AsRef<str>>,
+        size_hint: usize,
+    ) -> Self {
         let table = Vec::with_capacity(size_hint + 1);
         let mut out = Table {
             table,
-            grammar,
-            initial: initial.as_ref().to_owned(),
+            grammar: grammar.into(),
+            initials: initials
+                .into_iter()
+                .map(|x| x.as_ref().to_owned())
+                .collect(),
+            seed_everywhere: false,
         };
-        let initials = out.pred_phase(0, initials);
-        out.table.push(initials);
+        let initials = out.initial_items(0);
+        out.close(0, initials);
+        out
+    }
+
+    /// Also predicts the initial nonterminals at every later position, so that the table
+    /// contains everything that derives from it, wherever it starts
+    pub(super) fn seed_everywhere(mut self) -> Self {
+        self.seed_everywhere = true;
+        self
+    }
+
+    fn initial_items(&self, j: usize) -> HashMap<Item<T>, InsertedBy> {
+        let mut out = HashMap::new();
+        for initial in &self.initials {
+            let productions = self
+                .grammar
+                .productions
+                .get(initial)
+                .expect("grammar must contain initial");
+            out.extend(
+                productions
+                    .iter()
+                    .cloned()
+                    .map(|x| (Item::init(initial.clone(), x, j..j), InsertedBy::Pred)),
+            );
+        }
         out
     }
 
@@ -169,7 +230,7 @@ where
         &'a self,
         cur_state: &'a HashMap<Item<T>, InsertedBy>,
         idx: usize,
-    ) -> &HashMap<Item<T>, InsertedBy> {
+    ) -> &'a HashMap<Item<T>, InsertedBy> {
         if idx == self.table.len() {
             return cur_state;
         }
@@ -292,7 +353,110 @@ where
         // use axiom j-1,j,i[j-1] to advance in state j-1
         // -> keep advanced (scan)
         let mut cur_state = self.scan_phase(j, token);
+        if self.seed_everywhere {
+            cur_state.extend(self.initial_items(j));
+        }
+        self.close(j, cur_state);
+    }
 
+    /// Replaces the sets after `start` with empty ones up to `end`, and pushes set `end`, in
+    /// which every nonterminal of `recovery` that set `start` expects derives an error spanning
+    /// `start..end`. An empty error is added to set `start` itself, unless it would make some
+    /// nonterminal derive itself
+    pub(super) fn push_error(&mut self, start: usize, end: usize, recovery: &HashSet<String>) {
+        self.table.truncate(start + 1);
+        let errors: Vec<_> = self.table[start]
+            .keys()
+            .filter_map(|item| match item.after.last() {
+                Some(Token::NonTerm(nt)) if recovery.contains(nt) => Some((
+                    Item::init(nt.clone(), Vec::new(), start..end),
+                    InsertedBy::Error,
+                )),
+                _ => None,
+            })
+            .filter(|(item, _)| end > start || !self.cyclic_if_empty(&item.name))
+            .collect();
+        let mut cur_state = HashMap::new();
+        if end == start {
+            let set = self.table.pop().expect("set start was kept");
+            cur_state = Arc::unwrap_or_clone(set);
+        } else {
+            self.table.resize_with(end, Default::default);
+        }
+        for (item, inserted_by) in errors {
+            cur_state.entry(item).or_insert(inserted_by);
+        }
+        self.close(end, cur_state);
+    }
+
+    /// whether some nonterminal derives itself once `name` derives the empty string, e.g.
+    /// through `S -> S S`. The parse trees over an empty span then have no end
+    fn cyclic_if_empty(&self, name: &str) -> bool {
+        let productions = &self.grammar.productions;
+        let mut nullable = HashSet::from([name]);
+        loop {
+            let len = nullable.len();
+            for (lhs, expansions) in productions {
+                let empty = expansions.iter().any(|expansion| {
+                    expansion
+                        .iter()
+                        .all(|x| matches!(x, Token::NonTerm(nt) if nullable.contains(nt.as_str())))
+                });
+                if empty {
+                    nullable.insert(lhs);
+                }
+            }
+            if nullable.len() == len {
+                break;
+            }
+        }
+
+        // X derives Y on its own if X -> a Y b, with a and b empty
+        let empty =
+            |x: &Token<T>| matches!(x, Token::NonTerm(nt) if nullable.contains(nt.as_str()));
+        let derives = |x: &str| -> Vec<&str> {
+            let mut out = Vec::new();
+            for expansion in productions.get(x).into_iter().flatten() {
+                for (i, symbol) in expansion.iter().enumerate() {
+                    let Token::NonTerm(y) = symbol else {
+                        continue;
+                    };
+                    let (a, b) = (&expansion[..i], &expansion[i + 1..]);
+                    if a.iter().chain(b).all(empty) {
+                        out.push(y.as_str());
+                    }
+                }
+            }
+            out
+        };
+        // depth first search for a cycle
+        let mut done = HashSet::new();
+        for root in productions.keys() {
+            if done.contains(root.as_str()) {
+                continue;
+            }
+            let mut path = vec![root.as_str()];
+            let mut stack = vec![derives(root).into_iter()];
+            while let Some(next) = stack.last_mut() {
+                match next.next() {
+                    Some(y) if path.contains(&y) => return true,
+                    Some(y) if done.contains(y) => {}
+                    Some(y) => {
+                        path.push(y);
+                        stack.push(derives(y).into_iter());
+                    }
+                    None => {
+                        done.insert(path.pop().unwrap());
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// completes and predicts until nothing changes, then pushes the set
+    fn close(&mut self, j: usize, mut cur_state: HashMap<Item<T>, InsertedBy>) {
         loop {
             let init_len = cur_state.len();
             // # phase 2: comp
@@ -311,7 +475,45 @@ where
             }
         }
 
-        self.table.push(cur_state);
+        self.table.push(Arc::new(cur_state));
+    }
+
+    /// the first initial nonterminal that the tokens so far form a string of
+    pub(super) fn accepted(&self) -> Option<&str> {
+        let end = self.table.len() - 1;
+        let last = self.table.last().unwrap();
+        self.initials
+            .iter()
+            .find(|&initial| {
+                last.keys().any(|item| {
+                    item.name() == initial && item.after().is_empty() && item.range() == &(0..end)
+                })
+            })
+            .map(String::as_str)
+    }
+
+    /// whether the tokens so far form a string of an initial nonterminal
+    pub(super) fn is_complete(&self) -> bool {
+        self.accepted().is_some()
+    }
+
+    /// the parse of the tokens so far, if they are complete
+    pub(super) fn into_parse(self) -> Option<ParseInfo<T>> {
+        let initial = self.accepted()?.to_owned();
+        Some(ParseInfo {
+            table: self.table,
+            grammar: self.grammar,
+            initial,
+        })
+    }
+
+    /// an error for `token` at `position` that could not be pushed onto the table
+    pub(super) fn syntax_error(&self, position: usize, token: Option<T>) -> SyntaxError<T> {
+        SyntaxError {
+            position,
+            token,
+            expected: self.legal_tokens(),
+        }
     }
 
     pub(super) fn legal_tokens(&self) -> HashSet<T> {
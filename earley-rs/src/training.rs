@@ -0,0 +1,131 @@
+use std::{collections::HashMap, fmt::Display, hash::Hash};
+
+use crate::{parse_many, Grammar, SyntaxError};
+
+/// The result of [estimate_weights]
+pub struct Estimate<T> {
+    /// the grammar, with the estimated probabilities as the weights of its productions
+    pub grammar: Grammar<T>,
+    /// the log-likelihood of the corpus with the weights every iteration started with
+    pub log_likelihoods: Vec<f64>,
+}
+
+/// Estimates the probabilities of the productions of `grammar` from `corpus` by running
+/// `iterations` iterations of the inside-outside algorithm.
+///
+/// The weights of the productions of every nonterminal are normalized to sum to 1 and used as
+/// the starting point. Every iteration sets the probability of every production to the expected
+/// number of times it is used in the derivations of the corpus, divided by that of all productions
+/// of its nonterminal. The weights of nonterminals that are never used are kept, as are weights
+/// that sum to 0.
+///
+/// Fails with the index and the error of the first sentence of the corpus that does not parse.
+///
+/// # Examples
+///
+/// ```
+/// # use earley::*;
+/// let mut grammar = Grammar::new();
+/// grammar.add_prod("S", [Token::Term('a'), Token::NonTerm("S".into())]);
+/// grammar.add_prod("S", [Token::Term('b')]);
+///
+/// let estimate = estimate_weights(grammar, "S", ["aab", "b"].map(|x| x.chars()), 1).unwrap();
+/// // two of the four productions used are S -> a S
+/// let weight = estimate.grammar.weight("S", &[Token::Term('b')]).unwrap();
+/// assert!((weight - 0.5).abs() < 1e-9);
+/// ```
+pub fn estimate_weights<T, I>(
+    grammar: Grammar<T>,
+    initial: impl AsRef<str>,
+    corpus: impl IntoIterator<Item = I>,
+    iterations: usize,
+) -> Result<Estimate<T>, (usize, SyntaxError<T>)>
+where
+    I: IntoIterator<Item = T> + Send,
+    T: Clone + Eq + Hash + Display + Send + Sync,
+{
+    let mut weights: HashMap<String, Vec<f64>> = grammar.weights.clone();
+    for ws in weights.values_mut() {
+        normalize(ws);
+    }
+
+    let parses = parse_many(grammar.clone(), initial, corpus)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, parse)| parse.map_err(|e| (idx, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    // the forest of every sentence, with the production of every complete node
+    let forests: Vec<_> = parses
+        .iter()
+        .map(|parse| {
+            let forest = parse.forest();
+            let productions: Vec<_> = forest
+                .nodes
+                .iter()
+                .map(|(_, item, _)| {
+                    if !item.after().is_empty() {
+                        return None;
+                    }
+                    let idx = grammar.productions[item.name()]
+                        .iter()
+                        .position(|production| production == item.before())
+                        .expect("complete items are productions");
+                    Some((item.name(), idx))
+                })
+                .collect();
+            (forest, productions)
+        })
+        .collect();
+
+    let mut log_likelihoods = Vec::new();
+    for _ in 0..iterations {
+        let mut counts: HashMap<&str, Vec<f64>> = HashMap::new();
+        let mut log_likelihood = 0.0;
+        for (forest, productions) in &forests {
+            let node_weights: Vec<f64> = productions
+                .iter()
+                .map(|production| production.map_or(1.0, |(name, idx)| weights[name][idx]))
+                .collect();
+            let inside = forest.inside(&node_weights);
+            let total: f64 = forest.roots.iter().map(|&root| inside[root]).sum();
+            log_likelihood += total.ln();
+
+            let outside = forest.outside(&node_weights, &inside);
+            for (node, production) in productions.iter().enumerate() {
+                let Some((name, idx)) = *production else {
+                    continue;
+                };
+                let count = counts
+                    .entry(name)
+                    .or_insert_with(|| vec![0.0; weights[name].len()]);
+                count[idx] += inside[node] * outside[node] / total;
+            }
+        }
+        log_likelihoods.push(log_likelihood);
+
+        for (name, mut count) in counts {
+            if count.iter().sum::<f64>() > 0.0 {
+                normalize(&mut count);
+                weights.insert(name.to_owned(), count);
+            }
+        }
+    }
+
+    let mut grammar = grammar;
+    grammar.weights = weights;
+    Ok(Estimate {
+        grammar,
+        log_likelihoods,
+    })
+}
+
+/// scales `weights` to sum to 1, unless they sum to 0, then they are kept
+fn normalize(weights: &mut [f64]) {
+    let sum: f64 = weights.iter().sum();
+    if sum == 0.0 {
+        return;
+    }
+    for w in weights {
+        *w /= sum;
+    }
+}
@@ -0,0 +1,104 @@
+use std::{collections::HashMap, fmt::Display, hash::Hash};
+
+use crate::PrefixParser;
+
+/// A fixed list of entries, each of which is a sequence of tokens, used to constrain what may
+/// be pushed into a [PrefixParser] next.
+///
+/// The entries are stored in a trie, so entries with a common prefix are only checked once.
+///
+/// # Examples
+///
+/// ```
+/// # use earley::*;
+/// let mut grammar = Grammar::new();
+/// grammar.add_prod("S", [Token::Term('('), Token::NonTerm("S".into()), Token::Term(')')]);
+/// grammar.add_prod("S", [Token::Term('('), Token::Term(')')]);
+/// let vocabulary = Vocabulary::new(["(", "()", "))", ")(", ""].map(|x| x.chars()));
+///
+/// let mut parser = PrefixParser::new(grammar, "S");
+/// parser.try_next('(').unwrap();
+/// assert_eq!(vocabulary.mask(&parser), [true, true, false, false, true]);
+/// ```
+pub struct Vocabulary<T> {
+    nodes: Vec<Node<T>>,
+    len: usize,
+}
+
+struct Node<T> {
+    children: HashMap<T, usize>,
+    /// the entries ending at this node
+    entries: Vec<usize>,
+}
+
+impl<T> Node<T> {
+    fn new() -> Self {
+        Self {
+            children: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<T> Vocabulary<T>
+where
+    T: Clone + Eq + Hash + Display,
+{
+    pub fn new<E: IntoIterator<Item = T>>(entries: impl IntoIterator<Item = E>) -> Self {
+        let mut nodes = vec![Node::new()];
+        let mut len = 0;
+        for entry in entries {
+            let mut cur = 0;
+            for token in entry {
+                cur = match nodes[cur].children.get(&token) {
+                    Some(&child) => child,
+                    None => {
+                        nodes.push(Node::new());
+                        let child = nodes.len() - 1;
+                        nodes[cur].children.insert(token, child);
+                        child
+                    }
+                };
+            }
+            nodes[cur].entries.push(len);
+            len += 1;
+        }
+        Self { nodes, len }
+    }
+
+    /// the number of entries
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// For every entry, whether the parser could accept all of its tokens.
+    ///
+    /// The parser is left unchanged
+    pub fn mask(&self, parser: &PrefixParser<T>) -> Vec<bool> {
+        let mut mask = vec![false; self.len];
+        self.mask_node(0, &mut parser.fork(), &mut mask);
+        mask
+    }
+
+    fn mask_node(&self, node: usize, parser: &mut PrefixParser<T>, mask: &mut [bool]) {
+        let node = &self.nodes[node];
+        for &entry in &node.entries {
+            mask[entry] = true;
+        }
+        if node.children.is_empty() {
+            return;
+        }
+        let legal = parser.legal_tokens();
+        for (token, &child) in &node.children {
+            if !legal.contains(token) || parser.try_next(token.clone()).is_err() {
+                continue;
+            }
+            self.mask_node(child, parser, mask);
+            parser.undo().expect("a token was just pushed");
+        }
+    }
+}
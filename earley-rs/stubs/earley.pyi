@@ -1,4 +1,4 @@
-from typing import List, Set, overload
+from typing import Dict, List, Optional, Set, Tuple, overload
 
 
 class Token:
@@ -24,6 +24,8 @@ class Grammar:
 
     def add_prod(self, nonterm: str, expansion: List[Token]): ...
 
+    def add_weighted_prod(self, nonterm: str, expansion: List[Token], weight: float): ...
+
 
 class PrefixParser:
     progress: str
@@ -34,11 +36,50 @@ class PrefixParser:
     @overload
     def __new__(cls) -> Grammar: ...
 
+    @staticmethod
+    def with_probabilities(grammar: Grammar, initial: str) -> PrefixParser: ...
+
     def try_next(self, token: str): ...
 
+    def try_extend(self, tokens: List[str]): ...
+
+    def set_insertable(self, terminals: List[Tuple[str, int]], max: int): ...
+
+    def try_next_inserting(self, token: str) -> List[str]: ...
+
+    def accepts(self, tokens: List[str]) -> bool: ...
+
     def finish(self): ...
 
+    def is_complete(self) -> bool: ...
+
     def legal_tokens(self) -> Set[str]: ...
 
+    def prefix_probability(self) -> Optional[float]: ...
+
+    def next_token_distribution(self) -> Optional[Dict[str, float]]: ...
+
+    def shortest_completion(self) -> Optional[List[str]]: ...
+
+    def completions(self, max_len: int) -> List[List[str]]: ...
+
+    def fork(self) -> PrefixParser: ...
+
+    def undo(self): ...
+
+    def checkpoint(self) -> Checkpoint: ...
+
+    def rollback(self, checkpoint: Checkpoint): ...
+
+class Checkpoint:
+    ...
+
+class Vocabulary:
+    def __new__(cls, entries: List[List[str]]) -> Vocabulary: ...
+
+    def mask(self, parser: PrefixParser) -> List[bool]: ...
+
+    def __len__(self) -> int: ...
+
 class ParseError(Exception):
     ...